@@ -13,17 +13,22 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
+mod task_check;
 mod task_ls;
 
 use colored::Colorize;
 
 use crate::{
-    config::Config,
+    config::{Config, Task},
     console::{Level, Log},
-    exec::{get_task_at_path, resolve_paths, TaskPath},
+    exec::{get_task_at_path, get_task_directory, resolve_paths, TaskPath},
 };
 
-pub fn try_exec_builtin_task(config: &Config, target_task_path: &TaskPath) -> Option<bool> {
+pub fn try_exec_builtin_task(
+    logger: &impl Log,
+    config: &Config,
+    target_task_path: &TaskPath,
+) -> Option<bool> {
     if !target_task_path.built_in {
         return None;
     }
@@ -39,6 +44,7 @@ pub fn try_exec_builtin_task(config: &Config, target_task_path: &TaskPath) -> Op
             target_task_path.property.as_ref(),
         ) {
             ("ls", None) => task_ls::exec(config),
+            ("check", None) => task_check::exec(logger, config),
             _ => false,
         },
     )
@@ -55,78 +61,23 @@ pub fn try_exec_builtin_property(
 
     match property {
         "help" => {
-            let target_task_path_without_property = TaskPath {
-                namespace: target_task_path.namespace.clone(),
-                built_in: target_task_path.built_in,
-                name: target_task_path.name.clone(),
-                property: None,
-            };
-
-            print!(
-                "{} {} ",
-                "usage:".bold().dimmed().white(),
-                "wb".green().bold()
-            );
-
-            if let Some(usage) = &task.usage {
-                println!("{} {}", target_task_path_without_property, usage);
-            } else {
-                println!(
-                    "{} {}",
-                    target_task_path_without_property,
-                    "...".dimmed().white()
-                );
-            }
-
-            if let Some(description) = &task.description {
-                println!();
-                println!("{}", description);
-            }
-
-            if let Some(examples) = &task.examples {
-                println!();
-                println!("{}", "examples:".bold().dimmed().white());
-
-                let mut first = true;
-
-                for example in examples {
-                    if first {
-                        first = false;
-                    } else {
-                        println!();
-                    }
-
-                    if let Some(description) = &example.description {
-                        println!("  {} {}", "#".magenta(), description.magenta());
-                    }
-
-                    println!(
-                        "  {} {} {} {}",
-                        "$".dimmed().white(),
-                        "wb".green().bold(),
-                        target_task_path_without_property,
-                        example.run
-                    );
-                }
-            }
+            print_help(task, target_task_path);
 
             Some(true)
         }
         "description" => {
             if let Some(description) = &task.description {
-                println!("{}", description);
+                println!("{description}");
                 Some(true)
             } else {
-                logger.log_message(
-                    Level::Error,
-                    format!("task does not have a description set"),
-                );
+                logger.log_message(Level::Error, "task does not have a description set");
                 Some(false)
             }
         }
         "resolved-inputs" => {
             if let Some(inputs) = &task.inputs {
-                let paths = match resolve_paths(inputs) {
+                let paths = match resolve_paths(inputs, get_task_directory(config, task).as_deref())
+                {
                     Ok(paths) => paths,
                     Err(err) => {
                         logger.log_message(
@@ -148,16 +99,17 @@ pub fn try_exec_builtin_property(
         }
         "resolved-outputs" => {
             if let Some(outputs) = &task.outputs {
-                let paths = match resolve_paths(outputs) {
-                    Ok(paths) => paths,
-                    Err(err) => {
-                        logger.log_message(
-                            Level::Error,
-                            format!("error while resolving task outputs: {err}"),
-                        );
-                        return Some(false);
-                    }
-                };
+                let paths =
+                    match resolve_paths(outputs, get_task_directory(config, task).as_deref()) {
+                        Ok(paths) => paths,
+                        Err(err) => {
+                            logger.log_message(
+                                Level::Error,
+                                format!("error while resolving task outputs: {err}"),
+                            );
+                            return Some(false);
+                        }
+                    };
 
                 for path in paths {
                     println!("{}", path.display());
@@ -171,3 +123,60 @@ pub fn try_exec_builtin_property(
         _ => None,
     }
 }
+
+fn print_help(task: &Task, target_task_path: &TaskPath) {
+    let target_task_path_without_property = TaskPath {
+        namespace: target_task_path.namespace.clone(),
+        built_in: target_task_path.built_in,
+        name: target_task_path.name.clone(),
+        property: None,
+    };
+
+    print!(
+        "{} {} ",
+        "usage:".bold().dimmed().white(),
+        "wb".green().bold()
+    );
+
+    if let Some(usage) = &task.usage {
+        println!("{target_task_path_without_property} {usage}");
+    } else {
+        println!(
+            "{} {}",
+            target_task_path_without_property,
+            "...".dimmed().white()
+        );
+    }
+
+    if let Some(description) = &task.description {
+        println!();
+        println!("{description}");
+    }
+
+    if let Some(examples) = &task.examples {
+        println!();
+        println!("{}", "examples:".bold().dimmed().white());
+
+        let mut first = true;
+
+        for example in examples {
+            if first {
+                first = false;
+            } else {
+                println!();
+            }
+
+            if let Some(description) = &example.description {
+                println!("  {} {}", "#".magenta(), description.magenta());
+            }
+
+            println!(
+                "  {} {} {} {}",
+                "$".dimmed().white(),
+                "wb".green().bold(),
+                target_task_path_without_property,
+                example.run
+            );
+        }
+    }
+}
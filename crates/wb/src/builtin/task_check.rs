@@ -0,0 +1,312 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use crate::{
+    config::Config,
+    console::{Level, Log},
+    error::WorkbenchError,
+    exec::{TaskGraph, TaskPath},
+};
+
+pub fn exec(logger: &impl Log, config: &Config) -> bool {
+    let mut task_paths = get_task_paths(config);
+
+    task_paths.sort_by_key(ToString::to_string);
+
+    let mut valid = true;
+    let mut cycles = Vec::new();
+
+    // Building the graph of every task catches missing dependencies and cycles without running
+    // anything
+    for task_path in task_paths {
+        match TaskGraph::build(config, &task_path) {
+            Ok(_) => continue,
+            // A cycle is found again from every task in it and every task that depends on it, so
+            // it is only reported the first time
+            Err(WorkbenchError::DependencyCycle(cycle)) => {
+                let cycle = normalize_cycle(cycle);
+
+                if !cycles.contains(&cycle) {
+                    logger
+                        .log_message(Level::Error, WorkbenchError::DependencyCycle(cycle.clone()));
+                    cycles.push(cycle);
+                }
+            }
+            Err(err) => {
+                logger.log_message(Level::Error, format!("in task '{task_path}': {err}"));
+            }
+        }
+
+        valid = false;
+    }
+
+    if valid {
+        println!("configuration is valid");
+    }
+
+    valid
+}
+
+/// Rotates a cycle, which starts and ends with the same task, to start at its smallest task path so
+/// that it is the same whichever task it was found from
+fn normalize_cycle(mut cycle: Vec<TaskPath>) -> Vec<TaskPath> {
+    cycle.pop();
+
+    let start = cycle
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, task_path)| task_path.to_string())
+        .map_or(0, |(index, _)| index);
+
+    cycle.rotate_left(start);
+
+    if let Some(first) = cycle.first() {
+        cycle.push(first.clone());
+    }
+
+    cycle
+}
+
+fn get_task_paths(config: &Config) -> Vec<TaskPath> {
+    let mut task_paths = Vec::new();
+
+    if let Some(tasks) = &config.tasks {
+        for name in tasks.keys() {
+            task_paths.push(TaskPath {
+                namespace: None,
+                built_in: false,
+                name: name.clone(),
+                property: None,
+            });
+        }
+    }
+
+    if let Some(namespaces) = &config.namespaces {
+        for (namespace_name, namespace) in namespaces {
+            for name in namespace.tasks.keys() {
+                task_paths.push(TaskPath {
+                    namespace: Some(namespace_name.clone()),
+                    built_in: false,
+                    name: name.clone(),
+                    property: None,
+                });
+            }
+        }
+    }
+
+    task_paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Display,
+        path::PathBuf,
+        sync::{Arc, RwLock},
+        time::Duration,
+    };
+
+    use crate::{
+        config::{Namespace, Task},
+        exec::{Attempt, Output, PlannedTask, Stream, Timeout},
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct MockLogger {
+        messages: Arc<RwLock<Vec<(Level, String)>>>,
+    }
+
+    impl MockLogger {
+        fn take_messages(self) -> Vec<(Level, String)> {
+            Arc::try_unwrap(self.messages)
+                .unwrap()
+                .into_inner()
+                .unwrap()
+        }
+    }
+
+    impl Log for MockLogger {
+        fn log_message<Message: Display>(&self, level: Level, message: Message) {
+            self.messages
+                .write()
+                .unwrap()
+                .push((level, message.to_string()));
+        }
+
+        fn log_exec_output(
+            &self,
+            _task_path: &TaskPath,
+            _task: &Task,
+            _output: &Output,
+            _attempt: Attempt,
+        ) {
+        }
+
+        fn log_exec_output_line(
+            &self,
+            _task_path: &TaskPath,
+            _command: Option<usize>,
+            _stream: Stream,
+            _line: &[u8],
+        ) {
+        }
+
+        fn log_exec_timed_out(
+            &self,
+            _task_path: &TaskPath,
+            _task: &Task,
+            _output: &Output,
+            _timeout: Timeout,
+            _attempt: Attempt,
+        ) {
+        }
+
+        fn log_exec_cancelled(&self, _task_path: &TaskPath, _task: &Task, _output: &Output) {}
+
+        fn log_exec_skipped(&self, _task_path: &TaskPath, _reason: impl Display) {}
+
+        fn log_exec_dependencies_completed(&self, _task_path: &TaskPath, _dependencies: usize) {}
+
+        fn log_exec_allowed_failure(&self, _task_path: &TaskPath) {}
+
+        fn log_exec_finally(&self, _task_path: &TaskPath, _task: &Task, _output: &Output) {}
+
+        fn log_exec_planned(&self, _planned_task: &PlannedTask) {}
+
+        fn log_service_ready(&self, _task_path: &TaskPath, _task: &Task, _duration: Duration) {}
+
+        fn log_service_stopped(&self, _task_path: &TaskPath, _task: &Task, _output: &Output) {}
+
+        fn log_watch_waiting(&self) {}
+
+        fn log_watch_changed(&self, _paths: &[PathBuf]) {}
+    }
+
+    #[test]
+    fn test_exec_valid() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                ("a".to_owned(), Task::default()),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "ns:c".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            namespaces: Some(HashMap::from([(
+                "ns".to_owned(),
+                Namespace {
+                    tasks: HashMap::from([("c".to_owned(), Task::default())]),
+                    env: None,
+                    env_file: None,
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let logger = MockLogger::default();
+
+        assert!(exec(&logger, &config));
+        assert!(logger.take_messages().is_empty());
+    }
+
+    #[test]
+    fn test_exec_cycle() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let logger = MockLogger::default();
+
+        assert!(!exec(&logger, &config));
+        // The cycle is found from every task, but only reported once
+        assert_eq!(
+            logger.take_messages(),
+            vec![(
+                Level::Error,
+                "dependency cycle detected: a -> b -> a".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_normalize_cycle() {
+        let cycle = ["c", "a", "b", "c"]
+            .into_iter()
+            .map(|name| TaskPath::parse(name).unwrap())
+            .collect();
+
+        assert_eq!(
+            normalize_cycle(cycle),
+            ["a", "b", "c", "a"]
+                .into_iter()
+                .map(|name| TaskPath::parse(name).unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_exec_missing_dependency() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    dependencies: Some(vec!["missing".to_owned()]),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let logger = MockLogger::default();
+
+        assert!(!exec(&logger, &config));
+        assert_eq!(
+            logger.take_messages(),
+            vec![(
+                Level::Error,
+                "in task 'a': task missing not found".to_owned()
+            )]
+        );
+    }
+}
@@ -30,11 +30,11 @@ pub fn exec(config: &Config) -> bool {
 }
 
 fn get_task_names(config: &Config) -> Vec<String> {
-    let mut task_names = vec![":ls".to_owned()];
+    let mut task_names = vec![":check".to_owned(), ":ls".to_owned()];
 
     if let Some(tasks) = &config.tasks {
         for task_path in tasks.keys() {
-            task_names.push(task_path.to_string());
+            task_names.push(task_path.clone());
         }
     }
 
@@ -31,10 +31,12 @@ fn parse_args_from_vec(vec: Vec<String>) -> (WorkbenchArgs, TaskArgs) {
 
 #[cfg(test)]
 mod tests {
+    use crate::console::OutputMode;
+
     use super::*;
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "args vector must have at least one argument")]
     fn test_parse_args_from_vec_empty() {
         parse_args_from_vec(vec![]);
     }
@@ -51,6 +53,11 @@ mod tests {
                     disable_progress: false,
                     disable_color: false,
                     disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
                 },
                 TaskArgs {
                     target_task_path: None,
@@ -72,6 +79,11 @@ mod tests {
                     disable_progress: false,
                     disable_color: false,
                     disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
                 },
                 TaskArgs {
                     target_task_path: None,
@@ -93,6 +105,11 @@ mod tests {
                     disable_progress: false,
                     disable_color: false,
                     disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
                 },
                 TaskArgs {
                     target_task_path: Some("a".to_owned()),
@@ -119,6 +136,11 @@ mod tests {
                     disable_progress: false,
                     disable_color: false,
                     disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
                 },
                 TaskArgs {
                     target_task_path: Some("a".to_owned()),
@@ -147,6 +169,11 @@ mod tests {
                     disable_progress: false,
                     disable_color: false,
                     disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
                 },
                 TaskArgs {
                     target_task_path: Some("a".to_owned()),
@@ -155,4 +182,128 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_parse_args_from_vec_wb_output_stream_a() {
+        assert_eq!(
+            parse_args_from_vec(vec![
+                "wb".to_owned(),
+                "--output".to_owned(),
+                "stream".to_owned(),
+                "a".to_owned()
+            ]),
+            (
+                WorkbenchArgs {
+                    jobs: 0,
+                    verbose: false,
+                    config: None,
+                    disable_progress: false,
+                    disable_color: false,
+                    disable_unicode: false,
+                    output: OutputMode::Stream,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: false,
+                },
+                TaskArgs {
+                    target_task_path: Some("a".to_owned()),
+                    task_args: Vec::new()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_args_from_vec_wb_keep_going_a() {
+        assert_eq!(
+            parse_args_from_vec(vec![
+                "wb".to_owned(),
+                "--keep-going".to_owned(),
+                "a".to_owned()
+            ]),
+            (
+                WorkbenchArgs {
+                    jobs: 0,
+                    verbose: false,
+                    config: None,
+                    disable_progress: false,
+                    disable_color: false,
+                    disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: true,
+                    dry_run: false,
+                    watch: false,
+                },
+                TaskArgs {
+                    target_task_path: Some("a".to_owned()),
+                    task_args: Vec::new()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_args_from_vec_wb_n_a() {
+        assert_eq!(
+            parse_args_from_vec(vec!["wb".to_owned(), "-n".to_owned(), "a".to_owned()]),
+            (
+                WorkbenchArgs {
+                    jobs: 0,
+                    verbose: false,
+                    config: None,
+                    disable_progress: false,
+                    disable_color: false,
+                    disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: true,
+                    watch: false,
+                },
+                TaskArgs {
+                    target_task_path: Some("a".to_owned()),
+                    task_args: Vec::new()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_args_from_vec_wb_w_a() {
+        assert_eq!(
+            parse_args_from_vec(vec!["wb".to_owned(), "-w".to_owned(), "a".to_owned()]),
+            (
+                WorkbenchArgs {
+                    jobs: 0,
+                    verbose: false,
+                    config: None,
+                    disable_progress: false,
+                    disable_color: false,
+                    disable_unicode: false,
+                    output: OutputMode::Grouped,
+                    fail_fast: false,
+                    keep_going: false,
+                    dry_run: false,
+                    watch: true,
+                },
+                TaskArgs {
+                    target_task_path: Some("a".to_owned()),
+                    task_args: Vec::new()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_args_from_vec_wb_fail_fast_keep_going() {
+        let (workbench_args, _) = split_args::split_args(vec![
+            "wb".to_owned(),
+            "--fail-fast".to_owned(),
+            "--keep-going".to_owned(),
+        ]);
+
+        assert!(WorkbenchArgs::try_parse_from(workbench_args).is_err());
+    }
 }
@@ -58,14 +58,14 @@ fn collect_workbench_args<T: Iterator<Item = String>>(
         if let Some(arg) = arg {
             if arg.starts_with('-') {
                 // There is a flag or option that starts with '-', so we collect it
-                wb_args.push(arg.to_string());
+                wb_args.push(arg.clone());
                 args_iter.next();
 
                 // If the argument is an option that requires a value, we collect the value as well
                 if ARGS_WITH_VALUES.contains(arg.as_str()) {
                     let value = args_iter.next();
                     if let Some(value) = value {
-                        wb_args.push(value.to_string());
+                        wb_args.push(value);
                     } else {
                         break;
                     }
@@ -99,7 +99,7 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "args vector must have at least one argument")]
     fn test_no_args() {
         split_args(vec![]);
     }
@@ -117,7 +117,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -133,7 +133,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -149,7 +149,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -165,7 +165,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -191,7 +191,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -207,7 +207,7 @@ mod tests {
                     task_args: vec![],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -228,7 +228,7 @@ mod tests {
                     task_args: vec!["b".to_owned(), "c".to_owned()],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -250,7 +250,7 @@ mod tests {
                     task_args: vec!["b".to_owned(), "c".to_owned()],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -273,7 +273,7 @@ mod tests {
                     task_args: vec!["b".to_owned(), "c".to_owned()],
                 },
             )
-        )
+        );
     }
 
     #[test]
@@ -295,6 +295,6 @@ mod tests {
                     task_args: vec!["c".to_owned()],
                 },
             )
-        )
+        );
     }
 }
@@ -13,17 +13,18 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashSet;
-
-use lazy_static::lazy_static;
+use std::{collections::HashSet, sync::LazyLock};
 
 use clap::Parser;
 
+use crate::console::OutputMode;
+
 /// Command line arguments
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug, PartialEq, Eq)]
 #[command(version, about, long_about)]
 pub struct WorkbenchArgs {
-    /// The number of tasks to run in parallel
+    /// The maximum number of tasks to run in parallel (0 means the number of CPUs)
     #[arg(short, long, default_value_t = 0)]
     pub jobs: u32,
 
@@ -46,13 +47,34 @@ pub struct WorkbenchArgs {
     /// Whether or not to disable unicode characters in output
     #[arg(long, default_value_t = false)]
     pub disable_unicode: bool,
+
+    /// How to print the output of task commands
+    #[arg(long, value_enum, default_value_t = OutputMode::Grouped)]
+    pub output: OutputMode,
+
+    /// Stop every running task as soon as any task fails
+    #[arg(long, default_value_t = false, conflicts_with = "keep_going")]
+    pub fail_fast: bool,
+
+    /// Keep running every task that does not depend on a failed task
+    #[arg(long, default_value_t = false)]
+    pub keep_going: bool,
+
+    /// Print what each task would do, in the order they would run in, without running anything
+    #[arg(short = 'n', long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Run the task again whenever its inputs or the inputs of its dependencies change
+    #[arg(short = 'w', long, default_value_t = false, conflicts_with = "dry_run")]
+    pub watch: bool,
 }
 
-lazy_static! {
-    pub static ref ARGS_WITH_VALUES: HashSet<String> = HashSet::from([
+pub static ARGS_WITH_VALUES: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    HashSet::from([
         "-j".to_owned(),
         "--jobs".to_owned(),
         "-f".to_owned(),
-        "--config".to_owned()
-    ]);
-}
+        "--config".to_owned(),
+        "--output".to_owned(),
+    ])
+});
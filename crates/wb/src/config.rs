@@ -16,5 +16,5 @@
 mod domain;
 mod load;
 
-pub use domain::{Config, Files, Run, Shell, Task};
+pub use domain::{Command, Config, Files, Namespace, Ready, Retries, Run, Shell, Step, Task, When};
 pub use load::{load, resolve_path};
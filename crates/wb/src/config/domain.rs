@@ -13,26 +13,34 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, path::PathBuf};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct Config {
     pub tasks: Option<HashMap<String, Task>>,
     pub namespaces: Option<HashMap<String, Namespace>>,
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-#[serde(untagged)]
-pub enum NamespaceOrTask {
-    Namespace(Namespace),
-    Task(Task),
+    #[serde(default, deserialize_with = "deserialize_env")]
+    pub env: Option<HashMap<String, String>>,
+    pub env_file: Option<Vec<String>>,
+    /// The directory containing the config file, or `None` to use the current directory
+    #[serde(skip)]
+    pub directory: Option<PathBuf>,
+    /// The path that the config was loaded from, if any
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Namespace {
     pub tasks: HashMap<String, Task>,
+    #[serde(default, deserialize_with = "deserialize_env")]
+    pub env: Option<HashMap<String, String>>,
+    pub env_file: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -41,23 +49,126 @@ pub struct Example {
     pub run: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
 pub struct Task {
-    pub run: Run,
+    pub run: Option<Run>,
     pub shell: Option<Shell>,
+    pub interactive: Option<bool>,
+    /// Whether the task is a service that keeps running in the background while the tasks that depend
+    /// on it run, rather than running to completion
+    pub service: Option<bool>,
+    /// How to tell that a service is ready for the tasks that depend on it to start
+    pub ready: Option<Ready>,
+    /// Conditions that all have to be met for the task to run, or else it is skipped
+    pub when: Option<When>,
+    /// A command that runs once the task's command has finished, whether it succeeded, failed,
+    /// timed out or was cancelled, to clean up after it
+    pub finally: Option<Command>,
     pub dependencies: Option<Vec<String>>,
     pub inputs: Option<Files>,
     pub outputs: Option<Files>,
+    pub timeout: Option<u64>,
+    pub idle_timeout: Option<u64>,
+    pub retries: Option<Retries>,
+    /// The exit codes that count as the command succeeding, which is only 0 if this is not set
+    pub success_exit_codes: Option<Vec<i32>>,
+    /// Whether the task failing should be reported as a warning rather than failing the invocation
+    pub allow_failure: Option<bool>,
+    pub cwd: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_env")]
+    pub env: Option<HashMap<String, String>>,
+    pub env_file: Option<Vec<String>>,
     pub usage: Option<String>,
     pub description: Option<String>,
     pub examples: Option<Vec<Example>>,
 }
 
+impl Task {
+    pub fn is_success_exit_code(&self, exit_code: i32) -> bool {
+        self.success_exit_codes
+            .as_ref()
+            .map_or(exit_code == 0, |codes| codes.contains(&exit_code))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 #[serde(untagged)]
 pub enum Run {
     String(String),
     Args(Vec<String>),
+    /// Commands that run one after another, stopping at the first one that fails
+    Steps {
+        steps: Vec<Step>,
+    },
+    /// Commands that run at the same time, sharing the invocation's job slots
+    Parallel {
+        parallel: Vec<Step>,
+    },
+}
+
+impl From<Command> for Run {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::String(command) => Self::String(command),
+            Command::Args(args) => Self::Args(args),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum Step {
+    Command(Command),
+    Object { run: Command, shell: Option<Shell> },
+}
+
+impl Step {
+    pub const fn command(&self) -> &Command {
+        match self {
+            Self::Command(command) | Self::Object { run: command, .. } => command,
+        }
+    }
+
+    pub const fn shell(&self) -> Option<&Shell> {
+        match self {
+            Self::Command(_) => None,
+            Self::Object { shell, .. } => shell.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum Command {
+    String(String),
+    Args(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct When {
+    /// Environment variables that have to be set, to any value
+    pub env_set: Option<Vec<String>>,
+    /// Environment variables that have to be set to these values
+    #[serde(default, deserialize_with = "deserialize_env")]
+    pub env: Option<HashMap<String, String>>,
+    /// Paths that have to exist, relative to the task's directory
+    pub exists: Option<Vec<String>>,
+    /// A command that has to exit with 0, run with the task's shell, directory and environment
+    pub run: Option<Command>,
+}
+
+/// Checks that all have to pass before a service is ready, which it is as soon as it starts if
+/// there are none
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct Ready {
+    /// A TCP port on localhost that has to accept connections
+    pub port: Option<u16>,
+    /// A regular expression that a line of the service's output has to match
+    pub log: Option<String>,
+    /// A command that has to exit with 0, run with the task's shell, directory and environment
+    pub run: Option<Command>,
+    /// The maximum number of seconds to wait for the service to be ready
+    pub timeout: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -76,3 +187,96 @@ pub enum Files {
         exclude: Vec<String>,
     },
 }
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum Retries {
+    Count(u32),
+    Object {
+        count: u32,
+        delay: Option<u64>,
+        backoff: Option<u32>,
+    },
+}
+
+impl Retries {
+    pub const fn count(&self) -> u32 {
+        match self {
+            Self::Count(count) | Self::Object { count, .. } => *count,
+        }
+    }
+
+    /// How long to wait before the first retry, in seconds
+    pub const fn delay(&self) -> u64 {
+        match self {
+            Self::Count(_) | Self::Object { delay: None, .. } => 0,
+            Self::Object {
+                delay: Some(delay), ..
+            } => *delay,
+        }
+    }
+
+    /// What to multiply the delay by after each retry
+    pub const fn backoff(&self) -> u32 {
+        match self {
+            Self::Count(_) | Self::Object { backoff: None, .. } => 1,
+            Self::Object {
+                backoff: Some(backoff),
+                ..
+            } => *backoff,
+        }
+    }
+}
+
+/// Deserializes environment variables, whose values can be written as numbers or booleans as well
+/// as strings since YAML does not need them to be quoted
+fn deserialize_env<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<HashMap<String, String>>, D::Error> {
+    let env = Option::<HashMap<String, EnvValue>>::deserialize(deserializer)?;
+
+    Ok(env.map(|env| {
+        env.into_iter()
+            .map(|(name, value)| (name, value.0))
+            .collect()
+    }))
+}
+
+/// The value of an environment variable, converted to a string
+struct EnvValue(String);
+
+impl<'de> Deserialize<'de> for EnvValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EnvValueVisitor)
+    }
+}
+
+struct EnvValueVisitor;
+
+impl Visitor<'_> for EnvValueVisitor {
+    type Value = EnvValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string, number or boolean")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<EnvValue, E> {
+        Ok(EnvValue(value.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<EnvValue, E> {
+        Ok(EnvValue(value.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<EnvValue, E> {
+        Ok(EnvValue(value.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<EnvValue, E> {
+        Ok(EnvValue(value.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<EnvValue, E> {
+        Ok(EnvValue(value.to_string()))
+    }
+}
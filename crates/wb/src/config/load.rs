@@ -13,28 +13,34 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
-use lazy_static::lazy_static;
 use std::{
     fs::File,
-    path::{Path, PathBuf},
+    path::{self, Path, PathBuf},
+    sync::LazyLock,
 };
 
 use crate::error::WorkbenchError;
 
 use super::Config;
 
-lazy_static! {
-    static ref CONFIG_FILENAMES: Vec<&'static str> = vec![
+static CONFIG_FILENAMES: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
+    vec![
         "workbench.yaml",
         "workbench.yml",
         ".workbench.yaml",
-        ".workbench.yml"
-    ];
-}
+        ".workbench.yml",
+    ]
+});
 
 pub fn load(path: &Path) -> Result<Config, WorkbenchError> {
     let config_file = File::open(path)?;
-    let config: Config = serde_yaml::from_reader(config_file)?;
+    let mut config: Config = serde_yaml::from_reader(config_file)?;
+
+    let path = path::absolute(path)?;
+
+    // Tasks run relative to the config file rather than wherever Workbench was run from
+    config.directory = path.parent().map(Path::to_path_buf);
+    config.path = Some(path);
 
     Ok(config)
 }
@@ -92,17 +98,12 @@ fn find_config_file_in_directory(
 fn is_symlink_to_file(path: &Path) -> bool {
     if !path
         .symlink_metadata()
-        .map(|m| m.file_type().is_symlink())
-        .unwrap_or(false)
+        .is_ok_and(|m| m.file_type().is_symlink())
     {
         return false;
     }
 
-    if !path
-        .metadata()
-        .map(|m| m.file_type().is_file())
-        .unwrap_or(false)
-    {
+    if !path.metadata().is_ok_and(|m| m.file_type().is_file()) {
         return false;
     }
 
@@ -113,7 +114,7 @@ fn is_symlink_to_file(path: &Path) -> bool {
 mod tests {
     use std::{collections::HashMap, fs};
 
-    use crate::config::{Files, Run, Shell, Task};
+    use crate::config::{Command, Files, Ready, Retries, Run, Shell, Step, Task};
 
     use super::*;
 
@@ -180,7 +181,7 @@ mod tests {
 
         let symlink_path = temp_dir.path().join("symlink");
 
-        std::os::unix::fs::symlink(&PathBuf::from("this/file/does/not/exist"), &symlink_path)
+        std::os::unix::fs::symlink(PathBuf::from("this/file/does/not/exist"), &symlink_path)
             .unwrap();
 
         assert!(!is_symlink_to_file(&symlink_path));
@@ -195,7 +196,7 @@ mod tests {
         File::create(&file_path).unwrap();
 
         assert_eq!(
-            find_config_file_in_directory(&temp_dir.path(), None),
+            find_config_file_in_directory(temp_dir.path(), None),
             Some(file_path)
         );
     }
@@ -208,7 +209,7 @@ mod tests {
 
         File::create(&file_path).unwrap();
 
-        assert_eq!(find_config_file_in_directory(&temp_dir.path(), None), None,);
+        assert_eq!(find_config_file_in_directory(temp_dir.path(), None), None,);
     }
 
     #[test]
@@ -220,7 +221,7 @@ mod tests {
         File::create(&file_path).unwrap();
 
         assert_eq!(
-            find_config_file_in_directory(&temp_dir.path(), Some("asdf.yaml")),
+            find_config_file_in_directory(temp_dir.path(), Some("asdf.yaml")),
             Some(file_path)
         );
     }
@@ -234,7 +235,7 @@ mod tests {
         File::create(&file_path).unwrap();
 
         assert_eq!(
-            find_config_file_in_directory(&temp_dir.path(), Some("asdf.yaml")),
+            find_config_file_in_directory(temp_dir.path(), Some("asdf.yaml")),
             None,
         );
     }
@@ -247,7 +248,7 @@ mod tests {
 
         File::create(&file_path).unwrap();
 
-        assert_eq!(resolve_path(&temp_dir.path(), None), Some(file_path),);
+        assert_eq!(resolve_path(temp_dir.path(), None), Some(file_path),);
     }
 
     #[test]
@@ -291,8 +292,9 @@ mod tests {
         assert_eq!(
             load(file_path.as_path()).unwrap(),
             Config {
-                tasks: None,
-                namespaces: None
+                directory: Some(temp_dir.path().to_path_buf()),
+                path: Some(file_path.clone()),
+                ..Default::default()
             }
         );
     }
@@ -303,6 +305,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn test_load_simple() {
         let temp_dir = tempfile::tempdir().unwrap();
 
@@ -325,9 +328,16 @@ mod tests {
         b:
           shell: true
           run: "sleep 0.5"
+          retries:
+            count: 2
+            delay: 1
+            backoff: 2
         c:
           shell: true
           run: "sleep 1 && echo c && false"
+          timeout: 60
+          idle_timeout: 10
+          retries: 1
           dependencies:
             - a
             - b"#,
@@ -341,45 +351,218 @@ mod tests {
                     (
                         "a".to_owned(),
                         Task {
-                            run: Run::String("sleep 1 && cat input.txt > output.txt".to_owned()),
+                            run: Some(Run::String(
+                                "sleep 1 && cat input.txt > output.txt".to_owned()
+                            )),
                             shell: Some(Shell::Bool(true)),
-                            dependencies: None,
                             inputs: Some(Files::List(vec!["input.txt".to_owned()])),
                             outputs: Some(Files::List(vec!["output.txt".to_owned()])),
-                            description: None,
-                            examples: None,
-                            usage: None,
+                            ..Default::default()
                         }
                     ),
                     (
                         "b".to_owned(),
                         Task {
-                            run: Run::String("sleep 0.5".to_owned()),
+                            run: Some(Run::String("sleep 0.5".to_owned())),
                             shell: Some(Shell::Bool(true)),
-                            dependencies: None,
-                            inputs: None,
-                            outputs: None,
-                            description: None,
-                            examples: None,
-                            usage: None,
+                            retries: Some(Retries::Object {
+                                count: 2,
+                                delay: Some(1),
+                                backoff: Some(2),
+                            }),
+                            ..Default::default()
                         }
                     ),
                     (
                         "c".to_owned(),
                         Task {
-                            run: Run::String("sleep 1 && echo c && false".to_owned()),
+                            run: Some(Run::String("sleep 1 && echo c && false".to_owned())),
                             shell: Some(Shell::Bool(true)),
                             dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
-                            inputs: None,
-                            outputs: None,
-                            description: None,
-                            examples: None,
-                            usage: None,
+                            timeout: Some(60),
+                            idle_timeout: Some(10),
+                            retries: Some(Retries::Count(1)),
+                            ..Default::default()
                         }
                     )
                 ])),
-                namespaces: None
+                directory: Some(temp_dir.path().to_path_buf()),
+                path: Some(file_path.clone()),
+                ..Default::default()
             }
         );
     }
+
+    #[test]
+    fn test_load_steps() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let file_path = temp_dir.path().join("workbench.yaml");
+
+        fs::write(
+            &file_path,
+            r"tasks:
+        a:
+          run:
+            steps:
+              - echo one
+              - [cargo, build]
+              - run: cargo test
+                shell: /bin/bash
+        b:
+          run: [echo one, echo two]
+",
+        )
+        .unwrap();
+
+        let tasks = load(file_path.as_path()).unwrap().tasks.unwrap();
+
+        assert_eq!(
+            tasks["a"].run,
+            Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("echo one".to_owned())),
+                    Step::Command(Command::Args(vec!["cargo".to_owned(), "build".to_owned()])),
+                    Step::Object {
+                        run: Command::String("cargo test".to_owned()),
+                        shell: Some(Shell::String("/bin/bash".to_owned())),
+                    },
+                ]
+            })
+        );
+
+        // A list of strings is always a single command
+        assert_eq!(
+            tasks["b"].run,
+            Some(Run::Args(vec![
+                "echo one".to_owned(),
+                "echo two".to_owned()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_load_service() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let file_path = temp_dir.path().join("workbench.yaml");
+
+        fs::write(
+            &file_path,
+            r"tasks:
+        db:
+          run: postgres
+          service: true
+          ready:
+            port: 5432
+            log: accepting connections
+            run: [pg_isready]
+            timeout: 30
+",
+        )
+        .unwrap();
+
+        let tasks = load(file_path.as_path()).unwrap().tasks.unwrap();
+
+        assert_eq!(tasks["db"].service, Some(true));
+        assert_eq!(
+            tasks["db"].ready,
+            Some(Ready {
+                port: Some(5432),
+                log: Some("accepting connections".to_owned()),
+                run: Some(Command::Args(vec!["pg_isready".to_owned()])),
+                timeout: Some(30),
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_finally() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let file_path = temp_dir.path().join("workbench.yaml");
+
+        fs::write(
+            &file_path,
+            r"tasks:
+        e2e:
+          run: ./e2e.sh
+          finally: dropdb e2e
+",
+        )
+        .unwrap();
+
+        let tasks = load(file_path.as_path()).unwrap().tasks.unwrap();
+
+        assert_eq!(
+            tasks["e2e"].finally,
+            Some(Command::String("dropdb e2e".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_load_env_scalars() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let file_path = temp_dir.path().join("workbench.yaml");
+
+        fs::write(
+            &file_path,
+            "\
+env:
+  PORT: 5432
+namespaces:
+  db:
+    tasks: {}
+    env:
+      DEBUG: true
+tasks:
+  a:
+    run: ./a.sh
+    env:
+      RATIO: 0.5
+      NAME: a
+    when:
+      env:
+        CI: true
+",
+        )
+        .unwrap();
+
+        let config = load(file_path.as_path()).unwrap();
+
+        assert_eq!(
+            config.env,
+            Some(HashMap::from([("PORT".to_owned(), "5432".to_owned())]))
+        );
+        assert_eq!(
+            config.namespaces.unwrap()["db"].env,
+            Some(HashMap::from([("DEBUG".to_owned(), "true".to_owned())]))
+        );
+
+        let task = &config.tasks.unwrap()["a"];
+
+        assert_eq!(
+            task.env,
+            Some(HashMap::from([
+                ("RATIO".to_owned(), "0.5".to_owned()),
+                ("NAME".to_owned(), "a".to_owned()),
+            ]))
+        );
+        assert_eq!(
+            task.when.as_ref().unwrap().env,
+            Some(HashMap::from([("CI".to_owned(), "true".to_owned())]))
+        );
+    }
+
+    #[test]
+    fn test_load_env_invalid() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let file_path = temp_dir.path().join("workbench.yaml");
+
+        fs::write(&file_path, "env:\n  PATHS: [a, b]\n").unwrap();
+
+        assert!(load(file_path.as_path()).is_err());
+    }
 }
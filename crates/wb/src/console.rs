@@ -14,18 +14,22 @@
 // see <https://www.gnu.org/licenses/>.
 
 use std::{
+    ffi::OsString,
     fmt::{self, Display},
-    io::{self, Write},
+    io::{self, StdoutLock, Write},
+    path::PathBuf,
     sync::{Arc, Mutex},
     time::Duration,
 };
 
+use clap::ValueEnum;
 use colored::Colorize;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use shell_quote::QuoteExt;
 
 use crate::{
-    config::{Run, Task},
-    exec::{Output, TaskPath},
+    config::{Command, Run, Step, Task},
+    exec::{Attempt, Output, PlannedTask, Signal, StepOutput, Stream, TaskPath, Timeout},
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -49,12 +53,67 @@ impl Display for Level {
     }
 }
 
+/// How the output of task commands is written to the console
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputMode {
+    /// Print all of a task's output at once after it completes
+    #[default]
+    Grouped,
+    /// Print each line of a task's output as soon as it is produced
+    Stream,
+}
+
 pub trait Log {
     fn log_message<Message: Display>(&self, level: Level, message: Message);
 
-    fn log_exec_output(&self, task_path: &TaskPath, task: &Task, output: &Output);
+    fn log_exec_output(&self, task_path: &TaskPath, task: &Task, output: &Output, attempt: Attempt);
+
+    /// Logs a line of a task's output as soon as it is produced, along with the number of the
+    /// parallel command that wrote it if there is one
+    fn log_exec_output_line(
+        &self,
+        task_path: &TaskPath,
+        command: Option<usize>,
+        stream: Stream,
+        line: &[u8],
+    );
+
+    fn log_exec_timed_out(
+        &self,
+        task_path: &TaskPath,
+        task: &Task,
+        output: &Output,
+        timeout: Timeout,
+        attempt: Attempt,
+    );
+
+    fn log_exec_cancelled(&self, task_path: &TaskPath, task: &Task, output: &Output);
 
     fn log_exec_skipped(&self, task_path: &TaskPath, reason: impl Display);
+
+    /// Logs a task that has no command of its own once all of its dependencies have completed
+    fn log_exec_dependencies_completed(&self, task_path: &TaskPath, dependencies: usize);
+
+    /// Logs a task that failed but is allowed to, after its output has been logged
+    fn log_exec_allowed_failure(&self, task_path: &TaskPath);
+
+    /// Logs the output of a task's `finally` command, which is passed in as the task that it ran as
+    fn log_exec_finally(&self, task_path: &TaskPath, task: &Task, output: &Output);
+
+    /// Logs what a task would do during a dry run
+    fn log_exec_planned(&self, planned_task: &PlannedTask);
+
+    /// Logs a service that is ready for the tasks that depend on it to start
+    fn log_service_ready(&self, task_path: &TaskPath, task: &Task, duration: Duration);
+
+    /// Logs a service that was stopped once nothing needed it anymore
+    fn log_service_stopped(&self, task_path: &TaskPath, task: &Task, output: &Output);
+
+    /// Logs that watch mode is waiting for something to change before running again
+    fn log_watch_waiting(&self);
+
+    /// Logs the changed paths that are about to make watch mode run again
+    fn log_watch_changed(&self, paths: &[PathBuf]);
 }
 
 #[derive(Clone)]
@@ -66,6 +125,68 @@ impl Logger {
     pub const fn new(min_level: Level) -> Self {
         Self { min_level }
     }
+
+    /// Writes a command's output, under a heading for each step if the task has more than one
+    fn write_console_output(&self, task: &Task, output: &Output) {
+        let steps = match &task.run {
+            None | Some(Run::String(_) | Run::Args(_)) => None,
+            Some(Run::Steps { steps }) => Some((steps, "step")),
+            Some(Run::Parallel { parallel }) => Some((parallel, "command")),
+        };
+
+        let console_output = &output.console_output;
+        let mut stdout = io::stdout().lock();
+
+        let result = if let Some((steps, kind)) = steps {
+            steps.iter().zip(&output.steps).enumerate().try_for_each(
+                |(index, (step, step_output))| {
+                    writeln!(
+                        stdout,
+                        "  {} {}",
+                        "▸".dimmed(),
+                        format_step_message(task, step, kind, index + 1, steps.len(), step_output)
+                    )?;
+
+                    write_interleaved(
+                        &mut stdout,
+                        console_output.interleaved(step_output.chunks.clone()),
+                    )
+                },
+            )
+        } else {
+            write_interleaved(
+                &mut stdout,
+                console_output.interleaved(0..console_output.chunks.len()),
+            )
+        };
+
+        drop(stdout);
+
+        if let Err(e) = result {
+            self.log_message(Level::Error, e.to_string());
+        }
+    }
+}
+
+/// Writes each chunk of output to the stream that the command originally wrote it to, in the order
+/// that they were read
+fn write_interleaved<'a>(
+    stdout: &mut StdoutLock,
+    chunks: impl Iterator<Item = (Stream, &'a [u8])>,
+) -> Result<(), io::Error> {
+    for (stream, bytes) in chunks {
+        match stream {
+            Stream::Stdout => stdout.write_all(bytes)?,
+            Stream::Stderr => {
+                // Anything still buffered for stdout was written before this
+                stdout.flush()?;
+
+                io::stderr().write_all(bytes)?;
+            }
+        }
+    }
+
+    stdout.flush()
 }
 
 impl Log for Logger {
@@ -82,7 +203,13 @@ impl Log for Logger {
         }
     }
 
-    fn log_exec_output(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+    fn log_exec_output(
+        &self,
+        task_path: &TaskPath,
+        task: &Task,
+        output: &Output,
+        attempt: Attempt,
+    ) {
         if output.console_output.is_empty() {
             println!(
                 "{} {}",
@@ -91,8 +218,10 @@ impl Log for Logger {
                     task_path,
                     task,
                     output.exit_code,
+                    output.signal,
                     output.duration,
-                    false
+                    false,
+                    attempt
                 ),
             );
         } else {
@@ -103,23 +232,256 @@ impl Log for Logger {
                     task_path,
                     task,
                     output.exit_code,
+                    output.signal,
                     output.duration,
-                    true
+                    true,
+                    attempt
                 ),
                 ":".dimmed().white(),
             );
+        }
+
+        self.write_console_output(task, output);
+    }
 
-            if let Err(e) = io::stdout().write_all(&output.console_output) {
-                self.log_message(Level::Error, e.to_string());
+    fn log_exec_output_line(
+        &self,
+        task_path: &TaskPath,
+        command: Option<usize>,
+        stream: Stream,
+        line: &[u8],
+    ) {
+        // Lock stdout so that lines from tasks running in parallel are not interleaved
+        let mut stdout = io::stdout().lock();
+
+        let label = match command {
+            Some(number) => format!("'{task_path}' [{number}]"),
+            None => format!("'{task_path}'"),
+        };
+        let prefix = format!("{}{} ", label.green(), ":".dimmed());
+
+        let result = match stream {
+            Stream::Stdout => stdout
+                .write_all(prefix.as_bytes())
+                .and_then(|()| stdout.write_all(line))
+                .and_then(|()| writeln!(stdout)),
+            Stream::Stderr => {
+                let mut stderr = io::stderr().lock();
+
+                stderr
+                    .write_all(prefix.as_bytes())
+                    .and_then(|()| stderr.write_all(line))
+                    .and_then(|()| writeln!(stderr))
             }
+        };
+
+        drop(stdout);
+
+        if let Err(e) = result {
+            self.log_message(Level::Error, e.to_string());
         }
     }
 
+    fn log_exec_timed_out(
+        &self,
+        task_path: &TaskPath,
+        task: &Task,
+        output: &Output,
+        timeout: Timeout,
+        attempt: Attempt,
+    ) {
+        println!(
+            "{} {}{}",
+            "◆".green().dimmed(),
+            format_task_timeout_message(task_path, task, output.duration, timeout, attempt),
+            if output.console_output.is_empty() {
+                String::new()
+            } else {
+                ":".dimmed().white().to_string()
+            }
+        );
+
+        self.write_console_output(task, output);
+    }
+
+    fn log_exec_cancelled(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        println!(
+            "{} {}{} {}{}",
+            "◆".green().dimmed(),
+            format!("'{task_path}' cancelled").yellow(),
+            format!(" in {}:", format_duration(output.duration)).dimmed(),
+            format_run(task.run.as_ref()).dimmed(),
+            if output.console_output.is_empty() {
+                String::new()
+            } else {
+                ":".dimmed().white().to_string()
+            }
+        );
+
+        self.write_console_output(task, output);
+    }
+
     fn log_exec_skipped(&self, task_path: &TaskPath, reason: impl Display) {
         println!(
             "{} {}",
             "◆".green().dimmed(),
-            format!("'{}' skipped ({})", task_path, reason).yellow()
+            format!("'{task_path}' skipped ({reason})").yellow()
+        );
+    }
+
+    fn log_exec_dependencies_completed(&self, task_path: &TaskPath, dependencies: usize) {
+        println!(
+            "{} {}",
+            "◆".green().dimmed(),
+            format!(
+                "'{task_path}' completed ({dependencies} {})",
+                if dependencies == 1 {
+                    "dependency"
+                } else {
+                    "dependencies"
+                }
+            )
+            .green()
+        );
+    }
+
+    fn log_exec_allowed_failure(&self, task_path: &TaskPath) {
+        self.log_message(
+            Level::Warning,
+            format!("'{task_path}' failed, but is allowed to fail"),
+        );
+    }
+
+    fn log_exec_finally(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        let status = match output.timed_out {
+            Some(_) => format!("'{task_path}' cleanup timed out").red(),
+            None if output.succeeded(task) => format!("'{task_path}' cleanup completed").green(),
+            None => format!(
+                "'{task_path}' cleanup {}",
+                format_failure(output.exit_code, output.signal)
+            )
+            .red(),
+        };
+
+        println!(
+            "{} {status}{} {}{}",
+            "◆".green().dimmed(),
+            format!(" in {}:", format_duration(output.duration)).dimmed(),
+            format_run(task.run.as_ref()).dimmed(),
+            if output.console_output.is_empty() {
+                String::new()
+            } else {
+                ":".dimmed().white().to_string()
+            }
+        );
+
+        self.write_console_output(task, output);
+    }
+
+    fn log_exec_planned(&self, planned_task: &PlannedTask) {
+        let task_path = &planned_task.task_path;
+
+        let status = match &planned_task.skip_reason {
+            Some(reason) => format!("'{task_path}' would be skipped ({reason})").yellow(),
+            None if planned_task.commands.is_empty() => {
+                format!("'{task_path}' would complete once its dependencies have").green()
+            }
+            None => format!("'{task_path}' would run").green(),
+        };
+
+        if planned_task.commands.is_empty() {
+            println!("{} {status}", "◆".green().dimmed());
+
+            return;
+        }
+
+        println!(
+            "{} {status}{}",
+            "◆".green().dimmed(),
+            format!(
+                " in {}:",
+                planned_task.directory.as_ref().map_or_else(
+                    || ".".to_owned(),
+                    |directory| directory.display().to_string()
+                )
+            )
+            .dimmed()
+        );
+
+        if let Some(probe) = &planned_task.probe {
+            println!("  {} {}", "if".dimmed(), format_expanded_command(probe));
+        }
+
+        for command in &planned_task.commands {
+            println!(
+                "  {} {}",
+                if planned_task.parallel { "&" } else { "$" }.dimmed(),
+                format_expanded_command(command)
+            );
+        }
+
+        if let Some(finally) = &planned_task.finally {
+            println!(
+                "  {} {}",
+                "finally".dimmed(),
+                format_expanded_command(finally)
+            );
+        }
+    }
+
+    fn log_service_ready(&self, task_path: &TaskPath, task: &Task, duration: Duration) {
+        println!(
+            "{} {}{} {}",
+            "◆".green().dimmed(),
+            format!("'{task_path}' ready").green(),
+            format!(" in {}:", format_duration(duration)).dimmed(),
+            format_run(task.run.as_ref()).dimmed(),
+        );
+    }
+
+    fn log_service_stopped(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        println!(
+            "{} {}{} {}{}",
+            "◆".green().dimmed(),
+            format!("'{task_path}' stopped").green(),
+            format!(" in {}:", format_duration(output.duration)).dimmed(),
+            format_run(task.run.as_ref()).dimmed(),
+            if output.console_output.is_empty() {
+                String::new()
+            } else {
+                ":".dimmed().white().to_string()
+            }
+        );
+
+        self.write_console_output(task, output);
+    }
+
+    fn log_watch_waiting(&self) {
+        println!(
+            "{} {}",
+            "◆".green().dimmed(),
+            "waiting for changes...".dimmed()
+        );
+    }
+
+    fn log_watch_changed(&self, paths: &[PathBuf]) {
+        let Some(first) = paths.first() else {
+            return;
+        };
+
+        println!(
+            "{} {}",
+            "◆".green().dimmed(),
+            match paths.len() {
+                1 => format!("'{}' changed", first.display()),
+                2 => format!("'{}' and 1 other path changed", first.display()),
+                count => format!(
+                    "'{}' and {} other paths changed",
+                    first.display(),
+                    count - 1
+                ),
+            }
+            .cyan()
         );
     }
 }
@@ -129,7 +491,14 @@ pub trait Progress {
 
     fn complete_task(&self);
 
+    fn update_jobs(&self, in_use: usize, total: usize);
+
     fn clear(&self) -> Result<(), io::Error>;
+
+    /// Stops drawing progress while an interactive command is using the terminal
+    fn suspend(&self);
+
+    fn resume(&self);
 }
 
 #[derive(Clone)]
@@ -147,8 +516,10 @@ impl Context {
             let progress_bar_tasks = multi_progress.add(ProgressBar::new(progress_bar_ticks));
 
             progress_bar_tasks.set_style(
-                ProgressStyle::with_template("【{pos}/{len}】{bar:30.green/dim.white} {msg}")
-                    .expect("error with template"),
+                ProgressStyle::with_template(
+                    "【{pos}/{len}】{bar:30.green/dim.white} {prefix} {msg}",
+                )
+                .expect("error with template"),
             );
 
             let multi_progress = Arc::new(Mutex::new(multi_progress));
@@ -168,33 +539,10 @@ impl Context {
             }
         }
     }
-}
-
-impl Log for Context {
-    fn log_message<Message: Display>(&self, level: Level, message: Message) {
-        if level >= self.logger.min_level {
-            if let Some(multi_progress) = &self.multi_progress {
-                multi_progress
-                    .as_ref()
-                    .lock()
-                    .expect("progress bar mutex is poisoned")
-                    .clear()
-                    .unwrap();
-            }
-
-            self.logger.log_message(level, message);
 
-            if let Some(progress_bar_tasks) = &self.progress_bar_tasks {
-                progress_bar_tasks
-                    .as_ref()
-                    .lock()
-                    .expect("progress bar mutex is poisoned")
-                    .tick();
-            }
-        }
-    }
-
-    fn log_exec_output(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+    /// Clears the progress bars while the logger writes to the console so that they are not drawn
+    /// over, then draws them again
+    fn with_progress_cleared(&self, f: impl FnOnce(&Logger)) {
         if let Some(multi_progress) = &self.multi_progress {
             multi_progress
                 .as_ref()
@@ -204,7 +552,7 @@ impl Log for Context {
                 .unwrap();
         }
 
-        self.logger.log_exec_output(task_path, task, output);
+        f(&self.logger);
 
         if let Some(progress_bar_tasks) = &self.progress_bar_tasks {
             progress_bar_tasks
@@ -214,26 +562,92 @@ impl Log for Context {
                 .tick();
         }
     }
+}
 
-    fn log_exec_skipped(&self, task_path: &TaskPath, reason: impl Display) {
-        if let Some(multi_progress) = &self.multi_progress {
-            multi_progress
-                .as_ref()
-                .lock()
-                .expect("progress bar mutex is poisoned")
-                .clear()
-                .unwrap();
+impl Log for Context {
+    fn log_message<Message: Display>(&self, level: Level, message: Message) {
+        if level >= self.logger.min_level {
+            self.with_progress_cleared(|logger| logger.log_message(level, message));
         }
+    }
 
-        self.logger.log_exec_skipped(task_path, reason);
+    fn log_exec_output(
+        &self,
+        task_path: &TaskPath,
+        task: &Task,
+        output: &Output,
+        attempt: Attempt,
+    ) {
+        self.with_progress_cleared(|logger| {
+            logger.log_exec_output(task_path, task, output, attempt);
+        });
+    }
 
-        if let Some(progress_bar_tasks) = &self.progress_bar_tasks {
-            progress_bar_tasks
-                .as_ref()
-                .lock()
-                .expect("progress bar mutex is poisoned")
-                .tick();
-        }
+    fn log_exec_output_line(
+        &self,
+        task_path: &TaskPath,
+        command: Option<usize>,
+        stream: Stream,
+        line: &[u8],
+    ) {
+        self.with_progress_cleared(|logger| {
+            logger.log_exec_output_line(task_path, command, stream, line);
+        });
+    }
+
+    fn log_exec_timed_out(
+        &self,
+        task_path: &TaskPath,
+        task: &Task,
+        output: &Output,
+        timeout: Timeout,
+        attempt: Attempt,
+    ) {
+        self.with_progress_cleared(|logger| {
+            logger.log_exec_timed_out(task_path, task, output, timeout, attempt);
+        });
+    }
+
+    fn log_exec_cancelled(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        self.with_progress_cleared(|logger| logger.log_exec_cancelled(task_path, task, output));
+    }
+
+    fn log_exec_skipped(&self, task_path: &TaskPath, reason: impl Display) {
+        self.with_progress_cleared(|logger| logger.log_exec_skipped(task_path, reason));
+    }
+
+    fn log_exec_dependencies_completed(&self, task_path: &TaskPath, dependencies: usize) {
+        self.with_progress_cleared(|logger| {
+            logger.log_exec_dependencies_completed(task_path, dependencies);
+        });
+    }
+
+    fn log_exec_planned(&self, planned_task: &PlannedTask) {
+        self.logger.log_exec_planned(planned_task);
+    }
+
+    fn log_exec_allowed_failure(&self, task_path: &TaskPath) {
+        self.with_progress_cleared(|logger| logger.log_exec_allowed_failure(task_path));
+    }
+
+    fn log_exec_finally(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        self.with_progress_cleared(|logger| logger.log_exec_finally(task_path, task, output));
+    }
+
+    fn log_service_ready(&self, task_path: &TaskPath, task: &Task, duration: Duration) {
+        self.with_progress_cleared(|logger| logger.log_service_ready(task_path, task, duration));
+    }
+
+    fn log_service_stopped(&self, task_path: &TaskPath, task: &Task, output: &Output) {
+        self.with_progress_cleared(|logger| logger.log_service_stopped(task_path, task, output));
+    }
+
+    fn log_watch_waiting(&self) {
+        self.logger.log_watch_waiting();
+    }
+
+    fn log_watch_changed(&self, paths: &[PathBuf]) {
+        self.logger.log_watch_changed(paths);
     }
 }
 
@@ -249,11 +663,7 @@ impl Progress for Context {
                     "◆".green().dimmed(),
                     format!("'{task_path}'").green(),
                     ":".dimmed(),
-                    match &task.run {
-                        Run::String(value) => value.clone(),
-                        Run::Args(values) => values.join(" "),
-                    }
-                    .dimmed()
+                    format_run(task.run.as_ref()).dimmed()
                 ));
         }
     }
@@ -268,6 +678,16 @@ impl Progress for Context {
         }
     }
 
+    fn update_jobs(&self, in_use: usize, total: usize) {
+        if let Some(progress_bar_tasks) = &self.progress_bar_tasks {
+            progress_bar_tasks
+                .as_ref()
+                .lock()
+                .expect("progress bar mutex is poisoned")
+                .set_prefix(format!("{in_use}/{total} jobs").dimmed().to_string());
+        }
+    }
+
     fn clear(&self) -> Result<(), io::Error> {
         if let Some(multi_progress) = &self.multi_progress {
             multi_progress
@@ -279,42 +699,178 @@ impl Progress for Context {
 
         Ok(())
     }
+
+    fn suspend(&self) {
+        if let Some(multi_progress) = &self.multi_progress {
+            let multi_progress = multi_progress
+                .as_ref()
+                .lock()
+                .expect("progress bar mutex is poisoned");
+
+            multi_progress.clear().unwrap();
+            multi_progress.set_draw_target(ProgressDrawTarget::hidden());
+        }
+    }
+
+    fn resume(&self) {
+        if let Some(multi_progress) = &self.multi_progress {
+            multi_progress
+                .as_ref()
+                .lock()
+                .expect("progress bar mutex is poisoned")
+                .set_draw_target(ProgressDrawTarget::stderr());
+        }
+    }
 }
 
 fn format_task_completion_message(
     task_path: &TaskPath,
     task: &Task,
     exit_code: i32,
+    signal: Option<Signal>,
     duration: Duration,
     has_output: bool,
+    attempt: Attempt,
 ) -> impl Display {
-    if exit_code == 0 {
+    if signal.is_none() && task.is_success_exit_code(exit_code) {
         format!(
             "{}{} {}",
             format!(
-                "'{}' {}",
+                "'{}' {}{}",
                 task_path,
-                if has_output { "output" } else { "completed" }
+                if has_output { "output" } else { "completed" },
+                format_attempt(attempt)
             )
             .green(),
             format!(" in {}:", format_duration(duration)).dimmed(),
-            match &task.run {
-                Run::String(value) => value.clone(),
-                Run::Args(values) => values.join(" "),
-            }
-            .dimmed()
+            format_run(task.run.as_ref()).dimmed()
         )
     } else {
         format!(
             "{}{} {}",
-            format!("'{task_path}' failed with exit code {exit_code}").red(),
+            format!(
+                "'{task_path}' {}{}",
+                format_failure(exit_code, signal),
+                format_attempt(attempt)
+            )
+            .red(),
             format!(" in {}:", format_duration(duration)).dimmed(),
-            match &task.run {
-                Run::String(value) => value.clone(),
-                Run::Args(values) => values.join(" "),
-            }
-            .dimmed()
+            format_run(task.run.as_ref()).dimmed()
+        )
+    }
+}
+
+fn format_task_timeout_message(
+    task_path: &TaskPath,
+    task: &Task,
+    duration: Duration,
+    timeout: Timeout,
+    attempt: Attempt,
+) -> impl Display {
+    format!(
+        "{}{} {}",
+        match timeout {
+            Timeout::Total(limit) => format!(
+                "'{task_path}' timed out after {}{}",
+                format_duration(limit),
+                format_attempt(attempt)
+            ),
+            Timeout::Idle(limit) => format!(
+                "'{task_path}' timed out after producing no output for {}{}",
+                format_duration(limit),
+                format_attempt(attempt)
+            ),
+            Timeout::Ready(limit) => format!(
+                "'{task_path}' was not ready after {}{}",
+                format_duration(limit),
+                format_attempt(attempt)
+            ),
+        }
+        .red(),
+        format!(" in {}:", format_duration(duration)).dimmed(),
+        format_run(task.run.as_ref()).dimmed()
+    )
+}
+
+fn format_step_message(
+    task: &Task,
+    step: &Step,
+    kind: &str,
+    number: usize,
+    total: usize,
+    step_output: &StepOutput,
+) -> impl Display {
+    let status = match step_output.timed_out {
+        Some(_) => format!("{kind} {number}/{total} timed out").red(),
+        None if step_output.succeeded(task) => format!("{kind} {number}/{total} completed").green(),
+        None => format!(
+            "{kind} {number}/{total} {}",
+            format_failure(step_output.exit_code, step_output.signal)
         )
+        .red(),
+    };
+
+    format!(
+        "{status}{} {}",
+        format!(" in {}:", format_duration(step_output.duration)).dimmed(),
+        format_command(step.command()).dimmed()
+    )
+}
+
+fn format_failure(exit_code: i32, signal: Option<Signal>) -> String {
+    match signal {
+        Some(signal) if signal.core_dumped => format!("killed by {signal} (core dumped)"),
+        Some(signal) => format!("killed by {signal}"),
+        None => format!("failed with exit code {exit_code}"),
+    }
+}
+
+/// Formats a program and its arguments so that they could be pasted into a shell
+fn format_expanded_command(args: &[OsString]) -> String {
+    let mut buffer = OsString::new();
+
+    for (index, arg) in args.iter().enumerate() {
+        if index > 0 {
+            buffer.push(" ");
+        }
+
+        buffer.push_quoted(shell_quote::Sh, arg.as_os_str());
+    }
+
+    buffer.to_string_lossy().into_owned()
+}
+
+fn format_run(run: Option<&Run>) -> String {
+    match run {
+        None => String::new(),
+        Some(Run::String(value)) => value.clone(),
+        Some(Run::Args(values)) => values.join(" "),
+        Some(Run::Steps { steps }) => steps
+            .iter()
+            .map(|step| format_command(step.command()))
+            .collect::<Vec<_>>()
+            .join(" && "),
+        Some(Run::Parallel { parallel }) => parallel
+            .iter()
+            .map(|step| format_command(step.command()))
+            .collect::<Vec<_>>()
+            .join(" & "),
+    }
+}
+
+fn format_command(command: &Command) -> String {
+    match command {
+        Command::String(value) => value.clone(),
+        Command::Args(values) => values.join(" "),
+    }
+}
+
+fn format_attempt(attempt: Attempt) -> String {
+    // Tasks that are never retried do not need an attempt counter
+    if attempt.total > 1 {
+        format!(" (attempt {}/{})", attempt.number, attempt.total)
+    } else {
+        String::new()
     }
 }
 
@@ -322,7 +878,7 @@ fn format_duration(duration: Duration) -> impl Display {
     let secs = duration.as_secs_f64();
 
     if secs < 60.0 {
-        return format!("{:.2}s", secs);
+        return format!("{secs:.2}s");
     }
 
     let mins = secs / 60.0;
@@ -16,7 +16,7 @@
 use thiserror::Error;
 
 use super::TaskPath;
-use std::io;
+use std::{io, path::PathBuf};
 
 #[allow(clippy::module_name_repetitions)]
 #[derive(Error, Debug)]
@@ -31,12 +31,60 @@ pub enum WorkbenchError {
     FromGlobPatternError(#[from] glob::PatternError),
     #[error("{0}")]
     FromGlobError(#[from] glob::GlobError),
+    #[error("{0}")]
+    FromNotifyError(#[from] notify::Error),
+    #[error("{0}")]
+    FromRegexError(#[from] regex::Error),
+    #[error("unable to load environment file {0:?}: {1}")]
+    EnvFile(PathBuf, dotenvy::Error),
     #[error("invalid task path {0:?}")]
     InvalidTaskPath(String),
     #[error("task {0} not found")]
     TaskNotFound(TaskPath),
-    #[error("multiple errors occurred")]
+    #[error("multiple errors occurred:\n  {}", format_errors(.0))]
     Aggregate(Vec<Self>),
     #[error("shell required in task {0} for command {1:?}")]
     ShellRequired(TaskPath, String),
+    #[error("service task {0} has to run a single command")]
+    ServiceCommand(TaskPath),
+    #[error("task {0} runs its commands in parallel, so it cannot be given arguments")]
+    ParallelTaskArgs(TaskPath),
+    #[error("dependency cycle detected: {}", format_cycle(.0))]
+    DependencyCycle(Vec<TaskPath>),
+    #[error("interrupted")]
+    Interrupted,
+}
+
+fn format_errors(errors: &[WorkbenchError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n  ")
+}
+
+fn format_cycle(cycle: &[TaskPath]) -> String {
+    cycle
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate_display() {
+        let err = WorkbenchError::Aggregate(vec![
+            WorkbenchError::TaskNotFound(TaskPath::parse("a").unwrap()),
+            WorkbenchError::ServiceCommand(TaskPath::parse("b").unwrap()),
+        ]);
+
+        assert_eq!(
+            err.to_string(),
+            "multiple errors occurred:\n  task a not found\n  service task b has to run a single command"
+        );
+    }
 }
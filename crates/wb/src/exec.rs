@@ -13,140 +13,978 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
+mod conditions;
+mod environment;
 mod files;
+mod graph;
 mod handlers;
+mod jobs;
+mod plan;
+mod processes;
+mod service;
 mod shell;
 mod task_path;
+mod watch;
 
-use std::fs;
+use std::{collections::BTreeMap, fs, future, path::Path, sync::Arc, time::Duration};
 
-use futures::future::join_all;
-use tokio::runtime::Runtime;
+use tokio::{
+    runtime::Runtime,
+    signal::unix::{self, SignalKind},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::{self, JoinSet},
+    time::{self, Instant},
+};
 
 use crate::{
-    config::{Config, Files, Task},
-    console::{Log, Progress},
+    config::{Config, Files, Retries, Run, Step, Task},
+    console::{Level, Log, OutputMode, Progress},
     error::WorkbenchError,
 };
 
-use async_recursion::async_recursion;
+pub use task_path::{get_task_at_path, get_task_directory, TaskPath};
+
+pub use graph::{count_dependencies_of_path, Node, TaskGraph};
+
+pub use handlers::{Attempt, ConsoleOutput, Output, Signal, StepOutput, Stream, Timeout};
+
+pub use jobs::Jobs;
 
-pub use task_path::{count_dependencies_of_path, get_task_at_path, TaskPath};
+pub use plan::PlannedTask;
 
-pub use handlers::Output;
+pub use processes::Processes;
+
+use service::{ServiceHandle, Services};
 
 pub use self::files::resolve_paths;
 
+pub use watch::watch;
+
+/// What to do with the rest of the tasks once one of them fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Let the tasks that are already running finish, but do not start any new ones
+    #[default]
+    FinishRunning,
+    /// Cancel the tasks that are already running
+    FailFast,
+    /// Keep running every task that does not depend on a failed task
+    KeepGoing,
+}
+
+/// How a task, or a whole invocation, ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    /// A task failed, but is allowed to fail, so it is treated as having succeeded
+    AllowedFailure,
+    Failed,
+    /// A command was killed by a signal rather than exiting
+    Killed(Signal),
+}
+
+impl Outcome {
+    pub const fn succeeded(self) -> bool {
+        matches!(self, Self::Succeeded | Self::AllowedFailure)
+    }
+
+    /// The exit code for Workbench to exit with, which is 128 plus the signal's number if a command
+    /// was killed by one, as shells do
+    pub const fn exit_code(self) -> i32 {
+        match self {
+            Self::Succeeded | Self::AllowedFailure => 0,
+            Self::Failed => 1,
+            Self::Killed(signal) => handlers::EXIT_CODE_SIGNAL_OFFSET + signal.number,
+        }
+    }
+
+    /// Combines the outcomes of two tasks, keeping the first signal that killed either of them
+    const fn combine(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Killed(_), _) => self,
+            (_, Self::Killed(_)) => other,
+            (Self::Failed, _) | (_, Self::Failed) => Self::Failed,
+            (Self::AllowedFailure, _) | (_, Self::AllowedFailure) => Self::AllowedFailure,
+            (Self::Succeeded, Self::Succeeded) => Self::Succeeded,
+        }
+    }
+}
+
 pub fn exec<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
-    config: Config,
+    config: &Config,
     console_context: &ConsoleContext,
-    target_task_path: TaskPath,
-) -> Result<bool, WorkbenchError> {
-    let result = Runtime::new()?.block_on(exec_task(config, console_context, target_task_path));
+    target_task_path: &TaskPath,
+    task_args: &[String],
+    jobs: u32,
+    output_mode: OutputMode,
+    failure_mode: FailureMode,
+) -> Result<Outcome, WorkbenchError> {
+    let graph = TaskGraph::build(config, target_task_path)?;
+
+    graph.check_task_args(task_args)?;
+
+    let jobs = Jobs::new(jobs);
+
+    let result = Runtime::new()?.block_on(exec_graph(
+        &graph,
+        console_context,
+        task_args,
+        &jobs,
+        &Processes::default(),
+        output_mode,
+        failure_mode,
+    ));
 
     console_context.clear()?;
 
+    result.map(|(outcome, _)| outcome)
+}
+
+/// Logs what each task that the target task depends on would do, without running anything
+pub fn dry_run(
+    config: &Config,
+    console_context: &impl Log,
+    target_task_path: &TaskPath,
+    task_args: &[String],
+) -> Result<(), WorkbenchError> {
+    let graph = TaskGraph::build(config, target_task_path)?;
+
+    graph.check_task_args(task_args)?;
+
+    for planned_task in plan::plan(&graph, task_args)? {
+        console_context.log_exec_planned(&planned_task);
+    }
+
+    Ok(())
+}
+
+/// Runs every task in the graph, returning the outcome along with the indices of the tasks that
+/// succeeded
+async fn exec_graph<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    graph: &TaskGraph,
+    console_context: &ConsoleContext,
+    task_args: &[String],
+    jobs: &Jobs,
+    processes: &Processes,
+    output_mode: OutputMode,
+    failure_mode: FailureMode,
+) -> Result<(Outcome, Vec<usize>), WorkbenchError> {
+    let nodes = graph.nodes();
+
+    // The number of dependencies each task is still waiting on
+    let mut pending_dependencies = nodes
+        .iter()
+        .map(|node| node.dependencies.len())
+        .collect::<Vec<_>>();
+
+    let mut ready = (0..nodes.len())
+        .filter(|index| pending_dependencies[*index] == 0)
+        .collect::<Vec<_>>();
+
+    let mut running = JoinSet::new();
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    let mut allowed_failures = Vec::new();
+    let mut outcome = Outcome::Succeeded;
+    let mut errors = Vec::new();
+
+    let (mut services, mut ready_services) = Services::new(graph);
+
+    let mut cancellation = Cancellation::new()?;
+
+    // Unless asked to keep going, tasks that were already waiting for a job slot when something
+    // failed do not start either
+    if failure_mode != FailureMode::KeepGoing {
+        processes.stop_starting_on_failure();
+    }
+
+    loop {
+        let any_failures = !failed.is_empty() || !errors.is_empty();
+
+        // Unless asked to keep going, no new tasks are started once anything has failed
+        if (failure_mode == FailureMode::KeepGoing || !any_failures) && !processes.is_cancelled() {
+            for index in ready.drain(..) {
+                let node = nodes[index].clone();
+
+                // Arguments from the command line are only meant for the target task, which is
+                // always the last node
+                let task_args = if index == nodes.len() - 1 {
+                    task_args.to_vec()
+                } else {
+                    Vec::new()
+                };
+
+                let console_context = console_context.clone();
+                let jobs = jobs.clone();
+                let processes = processes.clone();
+                let service = (node.task.service == Some(true)).then(|| services.start(index));
+
+                running.spawn(async move {
+                    (
+                        index,
+                        exec_node(
+                            &console_context,
+                            &jobs,
+                            &processes,
+                            &node,
+                            &task_args,
+                            output_mode,
+                            service,
+                        )
+                        .await,
+                    )
+                });
+            }
+        }
+
+        services.stop_unused(running.len(), processes.is_cancelled());
+
+        let joined = tokio::select! {
+            // A service is always ready before it finishes, and its dependents have to be released
+            // before the invocation can be considered finished
+            biased;
+
+            Some(index) = ready_services.recv() => {
+                services.ready(index);
+                release_dependents(&nodes[index], &mut pending_dependencies, &mut ready);
+                continue;
+            }
+            joined = running.join_next() => joined,
+            () = cancellation.next(processes) => continue,
+        };
+
+        let Some(joined) = joined else {
+            break;
+        };
+
+        let (index, result) = joined?;
+
+        services.finish(&nodes[index], index);
+
+        match result {
+            Ok(task_outcome) if task_outcome.succeeded() => {
+                succeeded.push(index);
+
+                if task_outcome == Outcome::AllowedFailure {
+                    allowed_failures.push(&nodes[index].task_path);
+                }
+
+                // Services release their dependents as soon as they are ready instead
+                if nodes[index].task.service != Some(true) {
+                    release_dependents(&nodes[index], &mut pending_dependencies, &mut ready);
+                }
+            }
+            Ok(task_outcome) => {
+                outcome = outcome.combine(task_outcome);
+
+                failed.push(&nodes[index].task_path);
+            }
+            Err(err) => errors.push(err),
+        }
+
+        if failure_mode == FailureMode::FailFast && (!failed.is_empty() || !errors.is_empty()) {
+            cancellation.cancel(processes, libc::SIGTERM);
+        }
+    }
+
+    if cancellation.interrupted {
+        return Err(WorkbenchError::Interrupted);
+    }
+
+    if failure_mode == FailureMode::KeepGoing && !failed.is_empty() {
+        log_failures(console_context, &failed, &pending_dependencies);
+    }
+
+    log_allowed_failures(console_context, &allowed_failures);
+
+    match errors.len() {
+        0 => Ok((outcome, succeeded)),
+        1 => Err(errors.remove(0)),
+        _ => Err(WorkbenchError::Aggregate(errors)),
+    }
+}
+
+/// Marks a task as finished, readying any of its dependents that are no longer waiting on anything
+fn release_dependents(node: &Node, pending_dependencies: &mut [usize], ready: &mut Vec<usize>) {
+    // Each task runs exactly once, and its dependents become ready once every one of their
+    // dependencies has succeeded
+    for dependent in &node.dependents {
+        pending_dependencies[*dependent] -= 1;
+
+        if pending_dependencies[*dependent] == 0 {
+            ready.push(*dependent);
+        }
+    }
+}
+
+fn log_allowed_failures(console_context: &impl Log, allowed_failures: &[&TaskPath]) {
+    if allowed_failures.is_empty() {
+        return;
+    }
+
+    console_context.log_message(
+        Level::Warning,
+        format!(
+            "{} failed but {} allowed to fail: {}",
+            pluralize_tasks(allowed_failures.len()),
+            if allowed_failures.len() == 1 {
+                "is"
+            } else {
+                "are"
+            },
+            format_task_list(allowed_failures)
+        ),
+    );
+}
+
+fn log_failures(console_context: &impl Log, failed: &[&TaskPath], pending_dependencies: &[usize]) {
+    let failed_list = format_task_list(failed);
+
+    // Tasks that are still waiting on a dependency never ran because of a failure
+    let not_run = pending_dependencies
+        .iter()
+        .filter(|pending| **pending > 0)
+        .count();
+
+    console_context.log_message(
+        Level::Error,
+        if not_run == 0 {
+            format!("{} failed: {failed_list}", pluralize_tasks(failed.len()))
+        } else {
+            format!(
+                "{} failed: {failed_list} ({} not run because of a failed dependency)",
+                pluralize_tasks(failed.len()),
+                pluralize_tasks(not_run)
+            )
+        },
+    );
+}
+
+fn format_task_list(task_paths: &[&TaskPath]) -> String {
+    task_paths
+        .iter()
+        .map(|task_path| format!("'{task_path}'"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn pluralize_tasks(count: usize) -> String {
+    if count == 1 {
+        "1 task".to_owned()
+    } else {
+        format!("{count} tasks")
+    }
+}
+
+/// Cancels an invocation when Workbench is signalled, and kills anything that is still running once
+/// the grace period after it was cancelled is over
+struct Cancellation {
+    interrupt: unix::Signal,
+    terminate: unix::Signal,
+    /// Set once the invocation is cancelled, after which anything still running is killed
+    kill_deadline: Option<Instant>,
+    /// Whether Workbench was signalled, rather than a failure cancelling the invocation
+    interrupted: bool,
+}
+
+impl Cancellation {
+    fn new() -> Result<Self, WorkbenchError> {
+        Ok(Self {
+            interrupt: unix::signal(SignalKind::interrupt())?,
+            terminate: unix::signal(SignalKind::terminate())?,
+            kill_deadline: None,
+            interrupted: false,
+        })
+    }
+
+    fn cancel(&mut self, processes: &Processes, signal: i32) {
+        processes.cancel(signal);
+
+        // Only the first signal starts the grace period
+        if self.kill_deadline.is_none() {
+            self.kill_deadline = Some(Instant::now() + processes::KILL_GRACE_PERIOD);
+        }
+    }
+
+    /// Waits for Workbench to be signalled or for the grace period to be over, and acts on it
+    async fn next(&mut self, processes: &Processes) {
+        tokio::select! {
+            Some(()) = self.interrupt.recv() => {
+                // The terminal sends SIGINT to interactive commands as well, and it is up to them
+                // whether it should stop them
+                if !processes.is_terminal_locked() {
+                    self.cancel(processes, libc::SIGINT);
+                    self.interrupted = true;
+                }
+            }
+            Some(()) = self.terminate.recv() => {
+                self.cancel(processes, libc::SIGTERM);
+                self.interrupted = true;
+            }
+            () = time::sleep_until(self.kill_deadline.unwrap_or_else(Instant::now)),
+                if self.kill_deadline.is_some() =>
+            {
+                processes.kill();
+                self.kill_deadline = None;
+            }
+            else => future::pending().await,
+        }
+    }
+}
+
+/// Runs a task, or starts it in the background if it is a service
+async fn exec_node<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+    service: Option<ServiceHandle>,
+) -> Result<Outcome, WorkbenchError> {
+    let result = match service {
+        Some(service) => {
+            service::run(
+                console_context,
+                jobs,
+                processes,
+                node,
+                task_args,
+                output_mode,
+                service,
+            )
+            .await
+        }
+        None => {
+            exec_task(
+                console_context,
+                jobs,
+                processes,
+                node,
+                task_args,
+                output_mode,
+            )
+            .await
+        }
+    };
+
+    record_failure(processes, &result);
+
     result
 }
 
-#[async_recursion]
-async fn exec_task<ConsoleContext>(
-    config: Config,
+async fn exec_task<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
     console_context: &ConsoleContext,
-    task_path: TaskPath,
-) -> Result<bool, WorkbenchError>
-where
-    ConsoleContext: 'static + Log + Progress + Clone + Send + Sync,
-{
-    let task = task_path::get_task_at_path(&config, &task_path)
-        .ok_or_else(|| WorkbenchError::TaskNotFound(task_path.clone()))?;
+    jobs: &Jobs,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+) -> Result<Outcome, WorkbenchError> {
+    let task_path = &node.task_path;
+    let task = &node.task;
+
+    // Tasks without a command only group their dependencies, so there is nothing to take a job slot
+    if task.run.is_none() {
+        if processes.is_starting_stopped() {
+            return Ok(Outcome::Failed);
+        }
+
+        console_context.begin_task(task_path, task);
+
+        if let Some(reason) = check_conditions(node, processes).await? {
+            console_context.log_exec_skipped(task_path, reason);
+        } else {
+            console_context.log_exec_dependencies_completed(task_path, node.dependencies.len());
+        }
 
-    if !exec_all_dependencies(config.clone(), console_context, task.clone()).await? {
-        return Ok(false);
+        console_context.complete_task();
+
+        return Ok(Outcome::Succeeded);
+    }
+
+    // Wait for a free job slot before starting so that no more than `--jobs` tasks run at once
+    let mut permit = Some(jobs.acquire().await);
+
+    // Tasks that were waiting for a job slot when the invocation was cancelled, or when another
+    // task failed, never start
+    if processes.is_starting_stopped() {
+        return Ok(Outcome::Failed);
     }
 
-    console_context.begin_task(&task_path, task);
+    let result = exec_started_task(
+        console_context,
+        jobs,
+        &mut permit,
+        processes,
+        node,
+        task_args,
+        output_mode,
+    )
+    .await;
+
+    // Tasks waiting for a job slot could otherwise take this one before the failure is recorded
+    record_failure(processes, &result);
+
+    drop(permit);
 
-    let reason = should_run_task(task)?;
+    console_context.update_jobs(jobs.in_use(), jobs.total());
 
-    if let Some(reason) = reason {
-        console_context.log_exec_skipped(&task_path, reason);
+    result
+}
+
+/// Records a task that failed or could not be run, so that new tasks stop being started if they
+/// should
+fn record_failure(processes: &Processes, result: &Result<Outcome, WorkbenchError>) {
+    if !matches!(result, Ok(outcome) if outcome.succeeded()) {
+        processes.task_failed();
+    }
+}
+
+/// Runs a task that has been given a job slot
+async fn exec_started_task<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    permit: &mut Option<OwnedSemaphorePermit>,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+) -> Result<Outcome, WorkbenchError> {
+    let task_path = &node.task_path;
+    let task = &node.task;
+
+    console_context.update_jobs(jobs.in_use(), jobs.total());
+
+    console_context.begin_task(task_path, task);
+
+    let reason = match check_conditions(node, processes).await? {
+        Some(reason) => Some(reason),
+        None => should_run_task(task, node.directory.as_deref())?,
+    };
+
+    let outcome = if let Some(reason) = reason {
+        console_context.log_exec_skipped(task_path, reason);
 
         console_context.complete_task();
 
-        Ok(true)
+        Outcome::Succeeded
     } else {
-        let output = handlers::handle_execution(&task_path, task).unwrap();
-
-        console_context.log_exec_output(&task_path, task, &output);
+        let outcome = run_attempts(
+            console_context,
+            jobs,
+            permit,
+            processes,
+            node,
+            task_args,
+            output_mode,
+        )
+        .await;
+
+        // The cleanup command runs even if the task's command could not be run at all
+        let cleanup = run_finally(console_context, jobs, node, output_mode).await;
+
+        let mut outcome = outcome?.combine(cleanup?);
+
+        if !outcome.succeeded() && task.allow_failure == Some(true) && !processes.is_cancelled() {
+            console_context.log_exec_allowed_failure(task_path);
+
+            outcome = Outcome::AllowedFailure;
+        }
 
         console_context.complete_task();
 
-        Ok(output.exit_code == 0)
+        outcome
+    };
+
+    Ok(outcome)
+}
+
+/// Runs a task's command until it succeeds or runs out of retries, giving up the job slot while
+/// waiting between attempts
+async fn run_attempts<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    permit: &mut Option<OwnedSemaphorePermit>,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+) -> Result<Outcome, WorkbenchError> {
+    let task_path = &node.task_path;
+    let task = &node.task;
+
+    let total = task.retries.as_ref().map_or(0, Retries::count) + 1;
+    let mut delay = Duration::from_secs(task.retries.as_ref().map_or(0, Retries::delay));
+    let mut outcome = Outcome::Failed;
+
+    for number in 1..=total {
+        if number > 1 {
+            // Give up the job slot while waiting so that other tasks can run in the meantime
+            *permit = None;
+
+            console_context.update_jobs(jobs.in_use(), jobs.total());
+
+            // Cancelling the invocation cuts the wait short rather than waiting for it to be over
+            tokio::select! {
+                () = time::sleep(delay) => {}
+                () = processes.cancelled() => break,
+            }
+
+            delay *= task.retries.as_ref().map_or(1, Retries::backoff);
+
+            *permit = Some(jobs.acquire().await);
+
+            console_context.update_jobs(jobs.in_use(), jobs.total());
+
+            if processes.is_cancelled() {
+                break;
+            }
+        }
+
+        let output = run_attempt(
+            console_context,
+            jobs,
+            processes,
+            node,
+            task_args,
+            output_mode,
+        )
+        .await?;
+
+        let attempt = Attempt { number, total };
+
+        if processes.is_cancelled() {
+            console_context.log_exec_cancelled(task_path, task, &output);
+
+            break;
+        }
+
+        if let Some(timeout) = output.timed_out {
+            console_context.log_exec_timed_out(task_path, task, &output, timeout, attempt);
+        } else {
+            console_context.log_exec_output(task_path, task, &output, attempt);
+        }
+
+        outcome = get_outcome(task, &output);
+
+        if outcome.succeeded() {
+            break;
+        }
     }
+
+    Ok(outcome)
 }
 
-async fn exec_all_dependencies<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
-    config: Config,
+/// Runs a task's `finally` command once its own command has finished, however it ended
+///
+/// The command has its own processes, so that it still runs after the invocation has been
+/// cancelled. It is only limited by the task's timeouts.
+async fn run_finally<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
     console_context: &ConsoleContext,
-    task: Task,
-) -> Result<bool, WorkbenchError> {
-    if let Some(dependencies) = &task.dependencies {
-        let mut futures = Vec::new();
+    jobs: &Jobs,
+    node: &Node,
+    output_mode: OutputMode,
+) -> Result<Outcome, WorkbenchError> {
+    let Some(command) = &node.task.finally else {
+        return Ok(Outcome::Succeeded);
+    };
+
+    // It runs the same way as a `when` command does, but with its output shown
+    let node = Node {
+        task: conditions::get_probe_task(&node.task, command),
+        ..node.clone()
+    };
+
+    let mut environment = node.environment.clone();
+
+    environment::extend_run_environment(
+        &mut environment,
+        &node.task,
+        node.directory.as_deref(),
+        jobs.total(),
+    )?;
+
+    let output = run_blocking(
+        console_context.clone(),
+        Processes::default(),
+        node.clone(),
+        environment,
+        Vec::new(),
+        None,
+        output_mode,
+    )
+    .await?;
+
+    console_context.log_exec_finally(&node.task_path, &node.task, &output);
+
+    Ok(get_outcome(&node.task, &output))
+}
+
+/// How a task ended, going by the output of its command
+fn get_outcome(task: &Task, output: &Output) -> Outcome {
+    if output.succeeded(task) {
+        return Outcome::Succeeded;
+    }
+
+    // Commands killed because they timed out are reported as timed out instead
+    match output.signal {
+        Some(signal) if output.timed_out.is_none() => Outcome::Killed(signal),
+        _ => Outcome::Failed,
+    }
+}
+
+/// Runs a task's command once, with the terminal to itself if it is interactive
+async fn run_attempt<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+) -> Result<Output, WorkbenchError> {
+    let mut environment = node.environment.clone();
+
+    environment::extend_run_environment(
+        &mut environment,
+        &node.task,
+        node.directory.as_deref(),
+        jobs.total(),
+    )?;
+
+    // Only one command can have the terminal at a time, and the progress bar would otherwise draw
+    // over it
+    let terminal = if node.task.interactive == Some(true) {
+        let terminal = processes.lock_terminal().await;
+
+        console_context.suspend();
+
+        Some(terminal)
+    } else {
+        None
+    };
+
+    let output = run_command(
+        console_context,
+        jobs,
+        processes,
+        node,
+        environment,
+        task_args,
+        output_mode,
+    )
+    .await;
+
+    if terminal.is_some() {
+        console_context.resume();
+    }
+
+    output
+}
+
+async fn run_command<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    processes: &Processes,
+    node: &Node,
+    environment: BTreeMap<String, String>,
+    task_args: &[String],
+    output_mode: OutputMode,
+) -> Result<Output, WorkbenchError> {
+    // Arguments from the command line are rejected for parallel tasks before anything runs
+    let Some(Run::Parallel { parallel }) = &node.task.run else {
+        return run_blocking(
+            console_context.clone(),
+            processes.clone(),
+            node.clone(),
+            environment,
+            task_args.to_vec(),
+            None,
+            output_mode,
+        )
+        .await;
+    };
+
+    let start = Instant::now();
+
+    // One command runs in the task's own job slot, and the rest have to take slots from the ones
+    // shared by the invocation
+    let own_slot = Arc::new(Semaphore::new(1));
 
-        for dependency in dependencies {
-            futures.push(tokio::spawn(exec_dependency(
-                config.clone(),
+    let mut join_set = JoinSet::new();
+
+    for (index, step) in parallel.iter().enumerate() {
+        let console_context = console_context.clone();
+        let jobs = jobs.clone();
+        let processes = processes.clone();
+        let node = Node {
+            task: get_step_task(&node.task, step),
+            ..node.clone()
+        };
+        let environment = environment.clone();
+        let own_slot = own_slot.clone();
+
+        join_set.spawn(async move {
+            let permit = tokio::select! {
+                biased;
+
+                permit = own_slot.acquire_owned() => {
+                    permit.expect("task job slot semaphore is never closed")
+                }
+                permit = jobs.acquire() => permit,
+            };
+
+            console_context.update_jobs(jobs.in_use(), jobs.total());
+
+            let output = run_blocking(
                 console_context.clone(),
-                dependency.clone(),
-            )));
-        }
+                processes,
+                node,
+                environment,
+                Vec::new(),
+                Some(index + 1),
+                output_mode,
+            )
+            .await;
 
-        let results = join_all(futures.into_iter())
-            .await
-            .into_iter()
-            .map(|item| item?)
-            .collect::<Vec<_>>();
+            drop(permit);
 
-        let any_failures = results
-            .iter()
-            .any(|result| !result.as_ref().unwrap_or(&true));
+            console_context.update_jobs(jobs.in_use(), jobs.total());
+
+            (index, output)
+        });
+    }
+
+    let mut outputs = Vec::new();
+
+    while let Some(result) = join_set.join_next().await {
+        let (index, output) = result?;
+
+        outputs.push((index, output?));
+    }
+
+    // Output is attributed to commands in the order they were declared, not the order they finished
+    outputs.sort_by_key(|(index, _)| *index);
 
-        let errors = results
-            .into_iter()
-            .filter(Result::is_err)
-            .map(|item| item.unwrap_err())
-            .collect::<Vec<WorkbenchError>>();
+    let mut output = Output {
+        exit_code: 0,
+        signal: None,
+        console_output: ConsoleOutput::default(),
+        duration: start.elapsed(),
+        timed_out: None,
+        steps: Vec::new(),
+    };
+
+    let mut failed = false;
 
-        if !errors.is_empty() {
-            return Err(WorkbenchError::Aggregate(errors));
+    for (_, command_output) in outputs {
+        let chunks_start = output.console_output.chunks.len();
+
+        for chunk in &command_output.console_output.chunks {
+            output.console_output.push(
+                chunk.stream,
+                chunk.time,
+                command_output.console_output.bytes(chunk),
+            );
         }
 
-        if any_failures {
-            return Ok(false);
+        // The task fails with whichever of its failed commands was declared first
+        if !failed {
+            output.exit_code = command_output.exit_code;
+            output.signal = command_output.signal;
+            output.timed_out = command_output.timed_out;
+
+            failed = !command_output.succeeded(&node.task);
         }
+
+        output.steps.push(StepOutput {
+            exit_code: command_output.exit_code,
+            signal: command_output.signal,
+            chunks: chunks_start..output.console_output.chunks.len(),
+            duration: command_output.duration,
+            timed_out: command_output.timed_out,
+        });
     }
 
-    Ok(true)
+    Ok(output)
 }
 
-async fn exec_dependency<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
-    config: Config,
+async fn run_blocking<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
     console_context: ConsoleContext,
-    dependency: String,
-) -> Result<bool, WorkbenchError> {
-    exec_task(
-        config,
-        &console_context,
-        TaskPath::parse(dependency.as_str())?,
-    )
-    .await
+    processes: Processes,
+    node: Node,
+    environment: BTreeMap<String, String>,
+    task_args: Vec<String>,
+    command: Option<usize>,
+    output_mode: OutputMode,
+) -> Result<Output, WorkbenchError> {
+    // Commands block until they exit, so they run on the blocking pool to keep the runtime's worker
+    // threads free to schedule other tasks
+    task::spawn_blocking(move || {
+        let mut log_line = |stream: Stream, line: &[u8]| {
+            console_context.log_exec_output_line(&node.task_path, command, stream, line);
+        };
+
+        let on_line: Option<handlers::OnLine> = match output_mode {
+            OutputMode::Grouped => None,
+            OutputMode::Stream => Some(&mut log_line),
+        };
+
+        handlers::handle_execution(
+            &node.task_path,
+            &node.task,
+            &task_args,
+            node.directory.as_deref(),
+            &environment,
+            &processes,
+            on_line,
+        )
+    })
+    .await?
+}
+
+/// Checks a task's `when` conditions, returning why it should be skipped if any of them are not met
+async fn check_conditions(
+    node: &Node,
+    processes: &Processes,
+) -> Result<Option<String>, WorkbenchError> {
+    if node.task.when.is_none() {
+        return Ok(None);
+    }
+
+    let node = node.clone();
+    let processes = processes.clone();
+
+    // The probe command blocks until it exits, just like the task's own command
+    task::spawn_blocking(move || {
+        conditions::check_conditions(
+            &node.task_path,
+            &node.task,
+            node.directory.as_deref(),
+            &node.environment,
+            &processes,
+        )
+    })
+    .await?
+}
+
+/// The task that one of a task's parallel commands runs as, with everything but the command itself
+/// taken from the task
+fn get_step_task(task: &Task, step: &Step) -> Task {
+    Task {
+        run: Some(step.command().clone().into()),
+        shell: step.shell().or(task.shell.as_ref()).cloned(),
+        ..task.clone()
+    }
 }
 
-fn should_run_task(task: &Task) -> Result<Option<String>, WorkbenchError> {
+fn should_run_task(
+    task: &Task,
+    directory: Option<&Path>,
+) -> Result<Option<String>, WorkbenchError> {
     if let Some(inputs) = &task.inputs {
         if let Some(outputs) = &task.outputs {
-            if !should_run_tasks_files(inputs, outputs)? {
+            if !should_run_tasks_files(inputs, outputs, directory)? {
                 return Ok(Some("cached".to_owned()));
             }
         }
@@ -155,10 +993,14 @@ fn should_run_task(task: &Task) -> Result<Option<String>, WorkbenchError> {
     Ok(None)
 }
 
-fn should_run_tasks_files(inputs: &Files, outputs: &Files) -> Result<bool, WorkbenchError> {
+fn should_run_tasks_files(
+    inputs: &Files,
+    outputs: &Files,
+    directory: Option<&Path>,
+) -> Result<bool, WorkbenchError> {
     // Resolve paths
-    let input_paths = resolve_paths(inputs)?;
-    let output_paths = resolve_paths(outputs)?;
+    let input_paths = resolve_paths(inputs, directory)?;
+    let output_paths = resolve_paths(outputs, directory)?;
 
     // Find the timestamp of the most recently modified input file
     let mut input_most_recently_modified = None;
@@ -200,25 +1042,33 @@ mod tests {
         collections::HashMap,
         fmt::Display,
         io,
+        net::TcpListener,
+        path::PathBuf,
         sync::{Arc, RwLock},
     };
 
     use crate::{
-        config::Run,
+        config::{Command, Ready, Retries, Run, Shell, When},
         console::{Context, Level, Logger},
     };
 
     use super::*;
 
     #[derive(Clone, Debug, PartialEq)]
-    enum TaskOrderEntry {
+    pub(super) enum TaskOrderEntry {
         Begin(String),
         Complete(String),
+        AllowedFailure(String),
+        Finally(String),
+        Ready(String),
+        Stopped(String),
     }
 
     #[derive(Clone, Default)]
-    struct MockConsoleContext {
+    pub(super) struct MockConsoleContext {
         task_order: Arc<RwLock<Vec<TaskOrderEntry>>>,
+        max_jobs_in_use: Arc<RwLock<usize>>,
+        output_lines: Arc<RwLock<Vec<String>>>,
     }
 
     impl MockConsoleContext {
@@ -228,56 +1078,197 @@ mod tests {
                 .into_inner()
                 .unwrap()
         }
+
+        pub fn task_order(&self) -> Vec<TaskOrderEntry> {
+            self.task_order.read().unwrap().clone()
+        }
+
+        pub fn max_jobs_in_use(&self) -> usize {
+            *self.max_jobs_in_use.read().unwrap()
+        }
+
+        pub fn output_lines(&self) -> Vec<String> {
+            self.output_lines.read().unwrap().clone()
+        }
     }
 
     impl Log for MockConsoleContext {
         fn log_message<Message: Display>(&self, _level: Level, _message: Message) {}
 
-        fn log_exec_output(&self, task_path: &TaskPath, _task: &Task, _output: &Output) {
+        fn log_exec_output(
+            &self,
+            task_path: &TaskPath,
+            _task: &Task,
+            _output: &Output,
+            _attempt: Attempt,
+        ) {
             self.task_order
                 .write()
                 .unwrap()
                 .push(TaskOrderEntry::Complete(task_path.to_string()));
         }
 
-        fn log_exec_skipped(&self, task_path: &TaskPath, _reason: impl Display) {
+        fn log_exec_output_line(
+            &self,
+            _task_path: &TaskPath,
+            command: Option<usize>,
+            _stream: Stream,
+            line: &[u8],
+        ) {
+            let line = String::from_utf8_lossy(line);
+
+            self.output_lines.write().unwrap().push(match command {
+                Some(number) => format!("[{number}] {line}"),
+                None => line.into_owned(),
+            });
+        }
+
+        fn log_exec_cancelled(&self, task_path: &TaskPath, _task: &Task, _output: &Output) {
             self.task_order
                 .write()
                 .unwrap()
                 .push(TaskOrderEntry::Complete(task_path.to_string()));
         }
-    }
 
-    impl Progress for MockConsoleContext {
-        fn begin_task(&self, task_path: &TaskPath, _task: &Task) {
+        fn log_exec_timed_out(
+            &self,
+            task_path: &TaskPath,
+            _task: &Task,
+            _output: &Output,
+            _timeout: Timeout,
+            _attempt: Attempt,
+        ) {
             self.task_order
                 .write()
                 .unwrap()
-                .push(TaskOrderEntry::Begin(task_path.to_string()));
+                .push(TaskOrderEntry::Complete(task_path.to_string()));
         }
 
-        fn complete_task(&self) {}
-
-        fn clear(&self) -> Result<(), io::Error> {
-            Ok(())
+        fn log_exec_skipped(&self, task_path: &TaskPath, _reason: impl Display) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Complete(task_path.to_string()));
         }
-    }
 
-    #[test]
-    fn test_exec_target_task_path_not_found() {
-        let config = Config {
-            tasks: None,
-            namespaces: None,
-        };
+        fn log_exec_dependencies_completed(&self, task_path: &TaskPath, _dependencies: usize) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Complete(task_path.to_string()));
+        }
 
-        let console_context = Logger::new(Level::Status);
+        fn log_exec_planned(&self, _planned_task: &PlannedTask) {}
 
-        let console_context = Context::new(console_context, Some(1));
+        fn log_exec_allowed_failure(&self, task_path: &TaskPath) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::AllowedFailure(task_path.to_string()));
+        }
 
-        let result = exec(config, &console_context, TaskPath::parse("a").unwrap());
+        fn log_exec_finally(&self, task_path: &TaskPath, _task: &Task, _output: &Output) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Finally(task_path.to_string()));
+        }
 
-        assert!(result.is_err());
-    }
+        fn log_service_ready(&self, task_path: &TaskPath, _task: &Task, _duration: Duration) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Ready(task_path.to_string()));
+        }
+
+        fn log_service_stopped(&self, task_path: &TaskPath, _task: &Task, _output: &Output) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Stopped(task_path.to_string()));
+        }
+
+        fn log_watch_waiting(&self) {}
+
+        fn log_watch_changed(&self, _paths: &[PathBuf]) {}
+    }
+
+    impl Progress for MockConsoleContext {
+        fn begin_task(&self, task_path: &TaskPath, _task: &Task) {
+            self.task_order
+                .write()
+                .unwrap()
+                .push(TaskOrderEntry::Begin(task_path.to_string()));
+        }
+
+        fn complete_task(&self) {}
+
+        fn suspend(&self) {}
+
+        fn resume(&self) {}
+
+        fn update_jobs(&self, in_use: usize, _total: usize) {
+            let mut max_jobs_in_use = self.max_jobs_in_use.write().unwrap();
+
+            *max_jobs_in_use = (*max_jobs_in_use).max(in_use);
+        }
+
+        fn clear(&self) -> Result<(), io::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_exec_target_task_path_not_found() {
+        let config = Config {
+            ..Default::default()
+        };
+
+        let console_context = Logger::new(Level::Status);
+
+        let console_context = Context::new(console_context, Some(1));
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_exec_command_error() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::String("true".to_owned())),
+                    shell: Some(Shell::Bool(false)),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.is_err());
+    }
 
     #[test]
     fn test_exec_one_task() {
@@ -285,24 +1276,26 @@ mod tests {
             tasks: Some(HashMap::from([(
                 "a".to_owned(),
                 Task {
-                    run: Run::Args(vec!["true".to_owned()]),
-                    shell: None,
-                    dependencies: None,
-                    inputs: None,
-                    outputs: None,
-                    description: None,
-                    examples: None,
-                    usage: None,
+                    run: Some(Run::Args(vec!["true".to_owned()])),
+                    ..Default::default()
                 },
             )])),
-            namespaces: None,
+            ..Default::default()
         };
 
         let console_context = MockConsoleContext::default();
 
-        let result = exec(config, &console_context, TaskPath::parse("a").unwrap());
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
 
-        assert!(result.unwrap());
+        assert!(result.unwrap().succeeded());
 
         assert!(
             console_context.take_task_order()
@@ -320,38 +1313,82 @@ mod tests {
                 (
                     "a".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
-                        dependencies: None,
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("b").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Begin("b".to_owned()),
+                TaskOrderEntry::Complete("b".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_task_without_run() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        ..Default::default()
                     },
                 ),
                 (
                     "b".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
                         dependencies: Some(vec!["a".to_owned()]),
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        ..Default::default()
                     },
                 ),
             ])),
-            namespaces: None,
+            ..Default::default()
         };
 
         let console_context = MockConsoleContext::default();
 
-        let result = exec(config, &console_context, TaskPath::parse("b").unwrap());
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("b").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
 
-        assert!(result.unwrap());
+        assert!(result.unwrap().succeeded());
 
         assert_eq!(
             console_context.take_task_order(),
@@ -365,57 +1402,49 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn test_exec_task_with_two_dependencies() {
         let config = Config {
             tasks: Some(HashMap::from([
                 (
                     "a".to_owned(),
                     Task {
-                        run: Run::Args(vec!["sleep".to_owned(), "0.01".to_owned()]),
-                        shell: None,
-                        dependencies: None,
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "0.01".to_owned()])),
+                        ..Default::default()
                     },
                 ),
                 (
                     "b".to_owned(),
                     Task {
-                        run: Run::Args(vec!["sleep".to_owned(), "0.02".to_owned()]),
-                        shell: None,
-                        dependencies: None,
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "0.02".to_owned()])),
+                        ..Default::default()
                     },
                 ),
                 (
                     "c".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
+                        run: Some(Run::Args(vec!["true".to_owned()])),
                         dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        ..Default::default()
                     },
                 ),
             ])),
-            namespaces: None,
+            ..Default::default()
         };
 
         let console_context = MockConsoleContext::default();
 
-        let result = exec(config, &console_context, TaskPath::parse("c").unwrap());
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("c").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
 
-        assert!(result.unwrap());
+        assert!(result.unwrap().succeeded());
 
         let task_order = console_context.take_task_order();
 
@@ -444,57 +1473,50 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn test_exec_task_chain() {
         let config = Config {
             tasks: Some(HashMap::from([
                 (
                     "a".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
-                        dependencies: None,
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        ..Default::default()
                     },
                 ),
                 (
                     "b".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
+                        run: Some(Run::Args(vec!["true".to_owned()])),
                         dependencies: Some(vec!["a".to_owned()]),
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        ..Default::default()
                     },
                 ),
                 (
                     "c".to_owned(),
                     Task {
-                        run: Run::Args(vec!["true".to_owned()]),
-                        shell: None,
+                        run: Some(Run::Args(vec!["true".to_owned()])),
                         dependencies: Some(vec!["b".to_owned()]),
-                        inputs: None,
-                        outputs: None,
-                        description: None,
-                        examples: None,
-                        usage: None,
+                        ..Default::default()
                     },
                 ),
             ])),
-            namespaces: None,
+            ..Default::default()
         };
 
         let console_context = MockConsoleContext::default();
 
-        let result = exec(config, &console_context, TaskPath::parse("c").unwrap());
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("c").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
 
-        assert!(result.unwrap());
+        assert!(result.unwrap().succeeded());
 
         let task_order = console_context.take_task_order();
 
@@ -510,4 +1532,1390 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn test_exec_jobs_limit() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "0.01".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "0.01".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("c").unwrap(),
+            &[],
+            1,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        assert_eq!(console_context.max_jobs_in_use(), 1);
+
+        let task_order = console_context.take_task_order();
+
+        // With a single job slot, each dependency has to complete before the next one begins
+        assert!(
+            task_order
+                == vec![
+                    TaskOrderEntry::Begin("a".to_owned()),
+                    TaskOrderEntry::Complete("a".to_owned()),
+                    TaskOrderEntry::Begin("b".to_owned()),
+                    TaskOrderEntry::Complete("b".to_owned()),
+                    TaskOrderEntry::Begin("c".to_owned()),
+                    TaskOrderEntry::Complete("c".to_owned())
+                ]
+                || task_order
+                    == vec![
+                        TaskOrderEntry::Begin("b".to_owned()),
+                        TaskOrderEntry::Complete("b".to_owned()),
+                        TaskOrderEntry::Begin("a".to_owned()),
+                        TaskOrderEntry::Complete("a".to_owned()),
+                        TaskOrderEntry::Begin("c".to_owned()),
+                        TaskOrderEntry::Complete("c".to_owned())
+                    ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn test_exec_shared_dependency_runs_once() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "gen".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("c").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        let task_order = console_context.take_task_order();
+
+        assert_eq!(
+            task_order
+                .iter()
+                .filter(|entry| **entry == TaskOrderEntry::Begin("gen".to_owned()))
+                .count(),
+            1
+        );
+
+        assert_eq!(task_order.len(), 8);
+        assert_eq!(task_order[0], TaskOrderEntry::Begin("gen".to_owned()));
+        assert_eq!(task_order[1], TaskOrderEntry::Complete("gen".to_owned()));
+        assert_eq!(task_order[7], TaskOrderEntry::Complete("c".to_owned()));
+    }
+
+    #[test]
+    fn test_exec_failed_dependency_stops_dependents() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["false".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("b").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_killed_by_signal() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::String("kill -KILL $$".to_owned())),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let outcome = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        )
+        .unwrap();
+
+        assert_eq!(
+            outcome,
+            Outcome::Killed(Signal {
+                number: libc::SIGKILL,
+                core_dumped: false
+            })
+        );
+        assert_eq!(outcome.exit_code(), 137);
+    }
+
+    #[test]
+    fn test_exec_success_exit_codes() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("exit 1".to_owned())),
+                        success_exit_codes: Some(vec![0, 1]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("exit 0".to_owned())),
+                        dependencies: Some(vec!["a".to_owned()]),
+                        success_exit_codes: Some(vec![1]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("b").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        // 'a' exiting with 1 is allowed, but 'b' exiting with 0 is not
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Begin("b".to_owned()),
+                TaskOrderEntry::Complete("b".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_allow_failure() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["false".to_owned()])),
+                        allow_failure: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("b").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Succeeded);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::AllowedFailure("a".to_owned()),
+                TaskOrderEntry::Begin("b".to_owned()),
+                TaskOrderEntry::Complete("b".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_when_not_met() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let marker_path = temp_dir.path().join("marker");
+
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec![
+                        "touch".to_owned(),
+                        marker_path.to_string_lossy().into_owned(),
+                    ])),
+                    when: Some(When {
+                        exists: Some(vec!["this/file/does/not/exist".to_owned()]),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        // Skipped tasks count as having succeeded without running anything
+        assert!(result.unwrap().succeeded());
+        assert!(!marker_path.exists());
+    }
+
+    #[test]
+    fn test_exec_service() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "db".to_owned(),
+                    Task {
+                        run: Some(Run::String(
+                            "echo starting; sleep 0.5; echo ready; sleep 30".to_owned(),
+                        )),
+                        service: Some(true),
+                        ready: Some(Ready {
+                            log: Some("^ready$".to_owned()),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["db".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("test").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Succeeded);
+
+        // The service is stopped as soon as the task that needs it finishes
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("db".to_owned()),
+                TaskOrderEntry::Ready("db".to_owned()),
+                TaskOrderEntry::Begin("test".to_owned()),
+                TaskOrderEntry::Complete("test".to_owned()),
+                TaskOrderEntry::Stopped("db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_service_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let port = listener.local_addr().unwrap().port();
+
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "db".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "30".to_owned()])),
+                        service: Some(true),
+                        ready: Some(Ready {
+                            port: Some(port),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["db".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("test").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Succeeded);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("db".to_owned()),
+                TaskOrderEntry::Ready("db".to_owned()),
+                TaskOrderEntry::Begin("test".to_owned()),
+                TaskOrderEntry::Complete("test".to_owned()),
+                TaskOrderEntry::Stopped("db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_service_exits_before_ready() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "db".to_owned(),
+                    Task {
+                        run: Some(Run::String("exit 3".to_owned())),
+                        service: Some(true),
+                        ready: Some(Ready {
+                            log: Some("ready".to_owned()),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["db".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("test").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("db".to_owned()),
+                TaskOrderEntry::Complete("db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_service_ready_timeout() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "db".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["sleep".to_owned(), "30".to_owned()])),
+                        service: Some(true),
+                        ready: Some(Ready {
+                            run: Some(Command::Args(vec!["false".to_owned()])),
+                            timeout: Some(1),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["db".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("test").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("db".to_owned()),
+                TaskOrderEntry::Complete("db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_finally_after_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let marker_path = temp_dir.path().join("marker");
+
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["false".to_owned()])),
+                    finally: Some(Command::String(format!("touch {}", marker_path.display()))),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert!(marker_path.exists());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Finally("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_finally_after_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let marker_path = temp_dir.path().join("marker");
+
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["sleep".to_owned(), "10".to_owned()])),
+                    finally: Some(Command::String(format!("touch {}", marker_path.display()))),
+                    timeout: Some(1),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert!(marker_path.exists());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Finally("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_finally_failure() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["true".to_owned()])),
+                    finally: Some(Command::Args(vec!["false".to_owned()])),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        // The task's own command succeeded, but it did not clean up after itself
+        assert_eq!(result.unwrap(), Outcome::Failed);
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Finally("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_service_finally() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let marker_path = temp_dir.path().join("marker");
+
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "db".to_owned(),
+                    Task {
+                        run: Some(Run::String("echo ready; sleep 30".to_owned())),
+                        service: Some(true),
+                        ready: Some(Ready {
+                            log: Some("^ready$".to_owned()),
+                            ..Default::default()
+                        }),
+                        finally: Some(Command::String(format!("touch {}", marker_path.display()))),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "test".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["true".to_owned()])),
+                        dependencies: Some(vec!["db".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("test").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert_eq!(result.unwrap(), Outcome::Succeeded);
+
+        assert!(marker_path.exists());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("db".to_owned()),
+                TaskOrderEntry::Ready("db".to_owned()),
+                TaskOrderEntry::Begin("test".to_owned()),
+                TaskOrderEntry::Complete("test".to_owned()),
+                TaskOrderEntry::Stopped("db".to_owned()),
+                TaskOrderEntry::Finally("db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_retry_until_success() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let marker_path = temp_dir.path().join("marker");
+
+        // Fails the first time it runs and succeeds the second time
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::String(format!(
+                        "test -f {0} || {{ touch {0}; false; }}",
+                        marker_path.display()
+                    ))),
+                    retries: Some(Retries::Count(2)),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exec_retries_exhausted() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::String("false".to_owned())),
+                    retries: Some(Retries::Object {
+                        count: 2,
+                        delay: Some(0),
+                        backoff: Some(2),
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exec_cancel_during_retry_delay() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["false".to_owned()])),
+                    retries: Some(Retries::Object {
+                        count: 3,
+                        delay: Some(10),
+                        backoff: None,
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap();
+
+        let processes = Processes::default();
+
+        let console_context = MockConsoleContext::default();
+
+        let jobs = Jobs::new(4);
+
+        let start = Instant::now();
+
+        let cancel = {
+            let processes = processes.clone();
+
+            async move {
+                time::sleep(Duration::from_millis(500)).await;
+
+                processes.cancel(libc::SIGINT);
+            }
+        };
+
+        let (result, ()) = tokio::join!(
+            exec_graph(
+                &graph,
+                &console_context,
+                &[],
+                &jobs,
+                &processes,
+                OutputMode::Grouped,
+                FailureMode::FinishRunning,
+            ),
+            cancel
+        );
+
+        assert_eq!(result.unwrap(), (Outcome::Failed, Vec::new()));
+
+        // The task stops waiting to be retried as soon as the invocation is cancelled
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn test_exec_keep_going() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("false".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "d".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        dependencies: Some(vec!["a".to_owned(), "c".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("d").unwrap(),
+            &[],
+            1,
+            OutputMode::Grouped,
+            FailureMode::KeepGoing,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        // 'c' does not depend on 'a', so it still runs after 'a' fails, but 'd' never does
+        assert_eq!(
+            console_context.take_task_order(),
+            vec![
+                TaskOrderEntry::Begin("a".to_owned()),
+                TaskOrderEntry::Complete("a".to_owned()),
+                TaskOrderEntry::Begin("b".to_owned()),
+                TaskOrderEntry::Complete("b".to_owned()),
+                TaskOrderEntry::Begin("c".to_owned()),
+                TaskOrderEntry::Complete("c".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::too_many_lines)]
+    fn test_exec_finish_running() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("false".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("sleep 0.2".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "d".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        dependencies: Some(vec!["a".to_owned(), "c".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("d").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        let task_order = console_context.take_task_order();
+
+        // 'b' had already been started when 'a' failed, so it is left to finish, but 'c' never starts
+        assert!(task_order.contains(&TaskOrderEntry::Complete("b".to_owned())));
+        assert!(!task_order.contains(&TaskOrderEntry::Begin("c".to_owned())));
+    }
+
+    #[test]
+    fn test_exec_finish_running_one_job() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("false".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "d".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("d").unwrap(),
+            &[],
+            1,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        let task_order = console_context.take_task_order();
+
+        let failed_at = task_order
+            .iter()
+            .position(|entry| *entry == TaskOrderEntry::Complete("a".to_owned()))
+            .unwrap();
+
+        // Whichever tasks were still waiting for the only job slot when 'a' failed never start
+        assert!(!task_order[failed_at..]
+            .iter()
+            .any(|entry| matches!(entry, TaskOrderEntry::Begin(_))));
+    }
+
+    #[test]
+    fn test_exec_fail_fast() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("sleep 0.2 && false".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("sleep 10".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let start = Instant::now();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("c").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FailFast,
+        );
+
+        assert!(!result.unwrap().succeeded());
+
+        // 'b' is cancelled as soon as 'a' fails instead of sleeping for the full 10 seconds
+        assert!(start.elapsed() < processes::KILL_GRACE_PERIOD);
+    }
+
+    #[test]
+    fn test_exec_parallel() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Parallel {
+                        parallel: vec![
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        assert_eq!(console_context.max_jobs_in_use(), 3);
+    }
+
+    #[test]
+    fn test_exec_parallel_stream() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Parallel {
+                        parallel: vec![
+                            Step::Command(Command::String("echo a".to_owned())),
+                            Step::Command(Command::String("sleep 0.2; echo b".to_owned())),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Stream,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        assert_eq!(
+            console_context.output_lines(),
+            vec!["[1] a".to_owned(), "[2] b".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_exec_single_error() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["echo one".to_owned()])),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let result = exec(
+            &config,
+            &MockConsoleContext::default(),
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        // A lone error is returned as it is, rather than hidden inside an aggregate
+        assert!(matches!(result, Err(WorkbenchError::FromStdIoError(_))));
+    }
+
+    #[test]
+    fn test_exec_parallel_task_args() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Parallel {
+                            parallel: vec![Step::Command(Command::String("echo a".to_owned()))],
+                        }),
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("echo b".to_owned())),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &["x".to_owned()],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(matches!(result, Err(WorkbenchError::ParallelTaskArgs(_))));
+
+        // Nothing runs, not even the dependencies
+        assert!(console_context.take_task_order().is_empty());
+    }
+
+    #[test]
+    fn test_exec_parallel_jobs_limit() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Parallel {
+                        parallel: vec![
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                            Step::Command(Command::String("sleep 0.2".to_owned())),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            1,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(result.unwrap().succeeded());
+
+        // The commands take turns in the task's own job slot rather than waiting for more
+        assert_eq!(console_context.max_jobs_in_use(), 1);
+    }
+
+    #[test]
+    fn test_exec_parallel_failure() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Parallel {
+                        parallel: vec![
+                            Step::Command(Command::String("false".to_owned())),
+                            Step::Command(Command::String("true".to_owned())),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let result = exec(
+            &config,
+            &console_context,
+            &TaskPath::parse("a").unwrap(),
+            &[],
+            4,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        assert!(!result.unwrap().succeeded());
+    }
 }
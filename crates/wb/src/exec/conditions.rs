@@ -0,0 +1,259 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, env, path::Path};
+
+use crate::{
+    config::{Command, Task, When},
+    error::WorkbenchError,
+};
+
+use super::{handlers, Processes, TaskPath};
+
+/// Checks a task's `when` conditions, returning why the task should be skipped if any of them are
+/// not met
+///
+/// Environment variables are looked up in the task's environment before Workbench's own. The probe
+/// command, if there is one, is only run once every other condition is met and blocks until it
+/// exits.
+pub fn check_conditions(
+    task_path: &TaskPath,
+    task: &Task,
+    directory: Option<&Path>,
+    environment: &BTreeMap<String, String>,
+    processes: &Processes,
+) -> Result<Option<String>, WorkbenchError> {
+    if let Some(reason) = check_static_conditions(task, directory, environment) {
+        return Ok(Some(reason));
+    }
+
+    if let Some(command) = task.when.as_ref().and_then(|when| when.run.as_ref()) {
+        let probe = get_probe_task(task, command);
+
+        let output = handlers::handle_execution(
+            task_path,
+            &probe,
+            &[],
+            directory,
+            environment,
+            processes,
+            None,
+        )?;
+
+        if !output.succeeded(&probe) {
+            return Ok(Some(match (output.timed_out, output.signal) {
+                (Some(_), _) => "condition not met: command timed out".to_owned(),
+                (None, Some(signal)) => format!("condition not met: command killed by {signal}"),
+                (None, None) => format!(
+                    "condition not met: command exited with {}",
+                    output.exit_code
+                ),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The task that a command checking on a task runs as, which runs like the task's own command would,
+/// but only once and without its output being shown
+pub fn get_probe_task(task: &Task, command: &Command) -> Task {
+    Task {
+        run: Some(command.clone().into()),
+        interactive: None,
+        service: None,
+        ready: None,
+        when: None,
+        finally: None,
+        retries: None,
+        success_exit_codes: None,
+        ..task.clone()
+    }
+}
+
+/// Checks every one of a task's `when` conditions other than the probe command, which are the ones
+/// that can be checked without running anything
+pub fn check_static_conditions(
+    task: &Task,
+    directory: Option<&Path>,
+    environment: &BTreeMap<String, String>,
+) -> Option<String> {
+    let when = task.when.as_ref()?;
+
+    check_environment(when, environment).or_else(|| check_paths(when, directory))
+}
+
+fn check_environment(when: &When, environment: &BTreeMap<String, String>) -> Option<String> {
+    for name in when.env_set.iter().flatten() {
+        if lookup(environment, name).is_none() {
+            return Some(format!("condition not met: env {name} unset"));
+        }
+    }
+
+    // Sorted so that the same condition is always reported when several are not met
+    let mut env = when.env.iter().flatten().collect::<Vec<_>>();
+
+    env.sort();
+
+    for (name, value) in env {
+        match lookup(environment, name) {
+            None => return Some(format!("condition not met: env {name} unset")),
+            Some(actual) if actual != *value => {
+                return Some(format!("condition not met: env {name} is not '{value}'"));
+            }
+            Some(_) => {}
+        }
+    }
+
+    None
+}
+
+fn check_paths(when: &When, directory: Option<&Path>) -> Option<String> {
+    for path in when.exists.iter().flatten() {
+        let exists = match directory {
+            Some(directory) => directory.join(path).exists(),
+            None => Path::new(path).exists(),
+        };
+
+        if !exists {
+            return Some(format!("condition not met: '{path}' does not exist"));
+        }
+    }
+
+    None
+}
+
+fn lookup(environment: &BTreeMap<String, String>, name: &str) -> Option<String> {
+    environment
+        .get(name)
+        .cloned()
+        .or_else(|| env::var_os(name).map(|value| value.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use super::*;
+
+    fn check(
+        task: &Task,
+        directory: Option<&Path>,
+        environment: &[(&str, &str)],
+    ) -> Option<String> {
+        check_conditions(
+            &TaskPath::parse("a").unwrap(),
+            task,
+            directory,
+            &environment
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+            &Processes::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_conditions_env_set() {
+        let task = Task {
+            when: Some(When {
+                env_set: Some(vec!["WB_TEST_CONDITION".to_owned()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(check(&task, None, &[("WB_TEST_CONDITION", "")]), None);
+        assert_eq!(
+            check(&task, None, &[]),
+            Some("condition not met: env WB_TEST_CONDITION unset".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_check_conditions_env_value() {
+        let task = Task {
+            when: Some(When {
+                env: Some(HashMap::from([(
+                    "WB_TEST_CONDITION".to_owned(),
+                    "true".to_owned(),
+                )])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(check(&task, None, &[("WB_TEST_CONDITION", "true")]), None);
+        assert_eq!(
+            check(&task, None, &[("WB_TEST_CONDITION", "false")]),
+            Some("condition not met: env WB_TEST_CONDITION is not 'true'".to_owned())
+        );
+        assert_eq!(
+            check(&task, None, &[]),
+            Some("condition not met: env WB_TEST_CONDITION unset".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_check_conditions_exists() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(temp_dir.path().join("present"), "").unwrap();
+
+        let task = Task {
+            when: Some(When {
+                exists: Some(vec!["present".to_owned(), "absent".to_owned()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check(&task, Some(temp_dir.path()), &[]),
+            Some("condition not met: 'absent' does not exist".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_check_conditions_run() {
+        let met = Task {
+            when: Some(When {
+                run: Some(Command::String("test \"$A\" = a".to_owned())),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let not_met = Task {
+            when: Some(When {
+                run: Some(Command::String("exit 2".to_owned())),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(check(&met, None, &[("A", "a")]), None);
+        assert_eq!(
+            check(&not_met, None, &[]),
+            Some("condition not met: command exited with 2".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_check_conditions_none() {
+        assert_eq!(check(&Task::default(), None, &[]), None);
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, Files, Namespace, Task},
+    error::WorkbenchError,
+};
+
+use super::{files::resolve_paths, TaskPath};
+
+/// The environment variables that a task's command runs with, on top of Workbench's own
+///
+/// Variables are merged from the config, then the task's namespace, then the task itself, with
+/// later levels overriding earlier ones. Within each level, variables from `env_file` are loaded
+/// first and then overridden by `env`. The standard `WB_*` variables that describe the task are set
+/// last so that they can be relied upon.
+pub fn get_task_environment(
+    config: &Config,
+    task_path: &TaskPath,
+    task: &Task,
+) -> Result<BTreeMap<String, String>, WorkbenchError> {
+    let directory = config.directory.as_deref();
+
+    let mut environment = BTreeMap::new();
+
+    extend(
+        &mut environment,
+        directory,
+        config.env_file.as_ref(),
+        config.env.as_ref(),
+    )?;
+
+    let namespace: Option<&Namespace> = task_path
+        .namespace
+        .as_ref()
+        .and_then(|namespace| config.namespaces.as_ref()?.get(namespace));
+
+    if let Some(namespace) = namespace {
+        extend(
+            &mut environment,
+            directory,
+            namespace.env_file.as_ref(),
+            namespace.env.as_ref(),
+        )?;
+    }
+
+    extend(
+        &mut environment,
+        directory,
+        task.env_file.as_ref(),
+        task.env.as_ref(),
+    )?;
+
+    environment.insert("WB_TASK_PATH".to_owned(), task_path.to_string());
+    environment.insert(
+        "WB_NAMESPACE".to_owned(),
+        task_path.namespace.clone().unwrap_or_default(),
+    );
+
+    if let Some(directory) = directory {
+        environment.insert(
+            "WB_WORKSPACE_ROOT".to_owned(),
+            directory.to_string_lossy().into_owned(),
+        );
+    }
+
+    if let Some(path) = &config.path {
+        environment.insert(
+            "WB_CONFIG_PATH".to_owned(),
+            path.to_string_lossy().into_owned(),
+        );
+    }
+
+    Ok(environment)
+}
+
+/// Adds the standard `WB_*` variables that are only known once the task is about to run
+///
+/// Inputs and outputs are resolved just before each run so that they reflect files created by the
+/// task's dependencies.
+pub fn extend_run_environment(
+    environment: &mut BTreeMap<String, String>,
+    task: &Task,
+    directory: Option<&Path>,
+    jobs: usize,
+) -> Result<(), WorkbenchError> {
+    environment.insert("WB_JOBS".to_owned(), jobs.to_string());
+    environment.insert(
+        "WB_RESOLVED_INPUTS".to_owned(),
+        join_resolved_paths(task.inputs.as_ref(), directory)?,
+    );
+    environment.insert(
+        "WB_RESOLVED_OUTPUTS".to_owned(),
+        join_resolved_paths(task.outputs.as_ref(), directory)?,
+    );
+
+    Ok(())
+}
+
+fn join_resolved_paths(
+    files: Option<&Files>,
+    directory: Option<&Path>,
+) -> Result<String, WorkbenchError> {
+    let Some(files) = files else {
+        return Ok(String::new());
+    };
+
+    Ok(resolve_paths(files, directory)?
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn extend(
+    environment: &mut BTreeMap<String, String>,
+    directory: Option<&Path>,
+    env_files: Option<&Vec<String>>,
+    env: Option<&HashMap<String, String>>,
+) -> Result<(), WorkbenchError> {
+    for env_file in env_files.into_iter().flatten() {
+        // Like other paths in the config, env files are relative to the config file
+        let path = match directory {
+            Some(directory) => directory.join(env_file),
+            None => PathBuf::from(env_file),
+        };
+
+        let items = dotenvy::from_path_iter(&path)
+            .map_err(|err| WorkbenchError::EnvFile(path.clone(), err))?;
+
+        for item in items {
+            let (name, value) = item.map_err(|err| WorkbenchError::EnvFile(path.clone(), err))?;
+
+            environment.insert(name, value);
+        }
+    }
+
+    if let Some(env) = env {
+        environment.extend(env.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_get_task_environment_overrides() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(
+            temp_dir.path().join(".env"),
+            "FROM_CONFIG_FILE=config-file\nA=config-file\n",
+        )
+        .unwrap();
+
+        fs::write(
+            temp_dir.path().join("task.env"),
+            "A=task-file\nB=task-file\n",
+        )
+        .unwrap();
+
+        let config = Config {
+            namespaces: Some(HashMap::from([(
+                "ns".to_owned(),
+                Namespace {
+                    tasks: HashMap::from([(
+                        "a".to_owned(),
+                        Task {
+                            env: Some(HashMap::from([("B".to_owned(), "task".to_owned())])),
+                            env_file: Some(vec!["task.env".to_owned()]),
+                            ..Default::default()
+                        },
+                    )]),
+                    env: Some(HashMap::from([
+                        ("A".to_owned(), "namespace".to_owned()),
+                        ("C".to_owned(), "namespace".to_owned()),
+                    ])),
+                    env_file: None,
+                },
+            )])),
+            env: Some(HashMap::from([("A".to_owned(), "config".to_owned())])),
+            env_file: Some(vec![".env".to_owned()]),
+            directory: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let task_path = TaskPath::parse("ns:a").unwrap();
+
+        let environment = get_task_environment(
+            &config,
+            &task_path,
+            &config.namespaces.as_ref().unwrap()["ns"].tasks["a"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            environment,
+            BTreeMap::from([
+                ("FROM_CONFIG_FILE".to_owned(), "config-file".to_owned()),
+                ("A".to_owned(), "task-file".to_owned()),
+                ("B".to_owned(), "task".to_owned()),
+                ("C".to_owned(), "namespace".to_owned()),
+                ("WB_TASK_PATH".to_owned(), "ns:a".to_owned()),
+                ("WB_NAMESPACE".to_owned(), "ns".to_owned()),
+                (
+                    "WB_WORKSPACE_ROOT".to_owned(),
+                    temp_dir.path().to_string_lossy().into_owned()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_get_task_environment_standard_variables_take_precedence() {
+        let config = Config {
+            env: Some(HashMap::from([(
+                "WB_TASK_PATH".to_owned(),
+                "config".to_owned(),
+            )])),
+            directory: Some(PathBuf::from("/workspace")),
+            path: Some(PathBuf::from("/workspace/workbench.yaml")),
+            ..Default::default()
+        };
+
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let environment = get_task_environment(
+            &config,
+            &task_path,
+            &Task {
+                env: Some(HashMap::from([(
+                    "WB_NAMESPACE".to_owned(),
+                    "task".to_owned(),
+                )])),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            environment,
+            BTreeMap::from([
+                ("WB_TASK_PATH".to_owned(), "a".to_owned()),
+                ("WB_NAMESPACE".to_owned(), String::new()),
+                ("WB_WORKSPACE_ROOT".to_owned(), "/workspace".to_owned()),
+                (
+                    "WB_CONFIG_PATH".to_owned(),
+                    "/workspace/workbench.yaml".to_owned()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_extend_run_environment() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(temp_dir.path().join("a.txt"), "").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "").unwrap();
+
+        let task = Task {
+            inputs: Some(Files::List(vec!["*.txt".to_owned()])),
+            ..Default::default()
+        };
+
+        let mut environment = BTreeMap::new();
+
+        extend_run_environment(&mut environment, &task, Some(temp_dir.path()), 4).unwrap();
+
+        assert_eq!(
+            environment,
+            BTreeMap::from([
+                ("WB_JOBS".to_owned(), "4".to_owned()),
+                (
+                    "WB_RESOLVED_INPUTS".to_owned(),
+                    format!(
+                        "{}\n{}",
+                        temp_dir.path().join("a.txt").display(),
+                        temp_dir.path().join("b.txt").display()
+                    )
+                ),
+                ("WB_RESOLVED_OUTPUTS".to_owned(), String::new()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_get_task_environment_missing_env_file() {
+        let config = Config {
+            ..Default::default()
+        };
+
+        let task_path = TaskPath::parse("a").unwrap();
+
+        assert!(get_task_environment(
+            &config,
+            &task_path,
+            &Task {
+                env_file: Some(vec!["this/file/does/not/exist.env".to_owned()]),
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+}
@@ -13,16 +13,21 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{config::Files, error::WorkbenchError};
 
-pub fn resolve_paths(files: &Files) -> Result<Vec<PathBuf>, WorkbenchError> {
-    let include_patterns = resolve_include_patterns(files);
+pub fn resolve_paths(
+    files: &Files,
+    directory: Option<&Path>,
+) -> Result<Vec<PathBuf>, WorkbenchError> {
+    let include_patterns = resolve_include_patterns(files)
+        .into_iter()
+        .map(|pattern| relative_to(directory, pattern));
 
     let exclude_glob_patterns = resolve_exclude_patterns(files)
         .into_iter()
-        .map(|pattern| glob::Pattern::new(pattern.as_str()))
+        .map(|pattern| glob::Pattern::new(relative_to(directory, pattern).as_str()))
         .collect::<Result<Vec<glob::Pattern>, glob::PatternError>>()?;
 
     let mut result = Vec::new();
@@ -43,6 +48,91 @@ pub fn resolve_paths(files: &Files) -> Result<Vec<PathBuf>, WorkbenchError> {
     Ok(result)
 }
 
+/// Whether a path is one of the files, whether or not it exists
+pub fn matches_path(
+    files: &Files,
+    directory: Option<&Path>,
+    path: &Path,
+) -> Result<bool, WorkbenchError> {
+    let matches_any = |patterns: Vec<String>| -> Result<bool, WorkbenchError> {
+        // Like when resolving paths, wildcards only match within a single directory
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+
+        for pattern in patterns {
+            if glob::Pattern::new(relative_to(directory, pattern).as_str())?
+                .matches_path_with(path, options)
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    };
+
+    Ok(matches_any(resolve_include_patterns(files))?
+        && !matches_any(resolve_exclude_patterns(files))?)
+}
+
+/// The directories to watch for changes to the files, along with whether each one has to be
+/// watched recursively
+///
+/// Each pattern is watched from the deepest existing directory above its first wildcard, so that
+/// files which do not exist yet are noticed once they are created.
+pub fn resolve_watch_roots(files: &Files, directory: Option<&Path>) -> Vec<(PathBuf, bool)> {
+    resolve_include_patterns(files)
+        .into_iter()
+        .map(|pattern| {
+            let path = match directory {
+                Some(directory) => directory.join(pattern),
+                None => PathBuf::from(pattern),
+            };
+
+            let components = path.components().collect::<Vec<_>>();
+
+            let literal = components
+                .iter()
+                .position(|component| {
+                    component
+                        .as_os_str()
+                        .to_string_lossy()
+                        .contains(['*', '?', '['])
+                })
+                .unwrap_or(components.len());
+
+            // A wildcard anywhere but the last component can match files in nested directories
+            let recursive = components.len() - literal > 1 || path.to_string_lossy().contains("**");
+
+            let mut root = components[..literal].iter().collect::<PathBuf>();
+
+            while !root.is_dir() && root.pop() {}
+
+            // If part of the directory the files are in does not exist yet, they will be created
+            // somewhere below the root rather than directly inside it
+            let recursive = recursive || root.components().count() + 1 < components.len();
+
+            if root.as_os_str().is_empty() {
+                root.push(".");
+            }
+
+            (root, recursive)
+        })
+        .collect()
+}
+
+fn relative_to(directory: Option<&Path>, pattern: String) -> String {
+    match directory {
+        // The directory is escaped so that any glob characters in it are matched literally
+        Some(directory) if !Path::new(&pattern).is_absolute() => format!(
+            "{}/{pattern}",
+            glob::Pattern::escape(&directory.to_string_lossy())
+        ),
+        _ => pattern,
+    }
+}
+
 fn resolve_include_patterns(files: &Files) -> Vec<String> {
     match files {
         Files::List(patterns) => patterns
@@ -71,13 +161,15 @@ fn resolve_exclude_patterns(files: &Files) -> Vec<String> {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::*;
 
     #[test]
     fn test_resolve_files_list_empty() {
         let files = Files::List(Vec::new());
 
-        let paths = resolve_paths(&files).unwrap();
+        let paths = resolve_paths(&files, None).unwrap();
 
         assert_eq!(paths.len(), 0);
     }
@@ -86,8 +178,84 @@ mod tests {
     fn test_resolve_files_one_include_no_files() {
         let files = Files::List(Vec::new());
 
-        let paths = resolve_paths(&files).unwrap();
+        let paths = resolve_paths(&files, None).unwrap();
 
         assert_eq!(paths.len(), 0);
     }
+
+    #[test]
+    fn test_resolve_files_relative_to_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(temp_dir.path().join("a.txt"), "").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "").unwrap();
+
+        let files = Files::List(vec!["*.txt".to_owned(), "!b.txt".to_owned()]);
+
+        let paths = resolve_paths(&files, Some(temp_dir.path())).unwrap();
+
+        assert_eq!(paths, vec![temp_dir.path().join("a.txt")]);
+    }
+
+    #[test]
+    fn test_matches_path() {
+        let directory = Path::new("/workspace");
+
+        let files = Files::List(vec![
+            "src/**/*.rs".to_owned(),
+            "*.toml".to_owned(),
+            "!src/generated.rs".to_owned(),
+        ]);
+
+        let matches = |path: &str| matches_path(&files, Some(directory), Path::new(path)).unwrap();
+
+        assert!(matches("/workspace/src/main.rs"));
+        assert!(matches("/workspace/src/exec/files.rs"));
+        assert!(matches("/workspace/Cargo.toml"));
+        assert!(!matches("/workspace/src/generated.rs"));
+        assert!(!matches("/workspace/crates/Cargo.toml"));
+        assert!(!matches("/elsewhere/Cargo.toml"));
+    }
+
+    #[test]
+    fn test_resolve_watch_roots() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("Cargo.toml"), "").unwrap();
+
+        let files = Files::List(vec![
+            "src/**/*.rs".to_owned(),
+            "*.md".to_owned(),
+            "Cargo.toml".to_owned(),
+        ]);
+
+        assert_eq!(
+            resolve_watch_roots(&files, Some(temp_dir.path())),
+            vec![
+                (temp_dir.path().join("src"), true),
+                (temp_dir.path().to_path_buf(), false),
+                (temp_dir.path().to_path_buf(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_watch_roots_missing_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let files = Files::List(vec![
+            "missing/*.txt".to_owned(),
+            "missing/file.txt".to_owned(),
+        ]);
+
+        // The files would be created in a directory below the root, so it is watched recursively
+        assert_eq!(
+            resolve_watch_roots(&files, Some(temp_dir.path())),
+            vec![
+                (temp_dir.path().to_path_buf(), true),
+                (temp_dir.path().to_path_buf(), true),
+            ]
+        );
+    }
 }
@@ -0,0 +1,477 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use crate::{
+    config::{Config, Run, Task},
+    error::WorkbenchError,
+};
+
+use super::{environment::get_task_environment, get_task_at_path, get_task_directory, TaskPath};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub task_path: TaskPath,
+    pub task: Task,
+    pub directory: Option<PathBuf>,
+    pub environment: BTreeMap<String, String>,
+    pub dependencies: Vec<usize>,
+    pub dependents: Vec<usize>,
+}
+
+/// The transitive dependencies of a target task, with each task appearing exactly once
+///
+/// Nodes are stored in dependency order, so every node comes after all of its dependencies and the
+/// target task is always last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskGraph {
+    nodes: Vec<Node>,
+}
+
+impl TaskGraph {
+    pub fn build(config: &Config, target_task_path: &TaskPath) -> Result<Self, WorkbenchError> {
+        let mut graph = Self { nodes: Vec::new() };
+        let mut indices = HashMap::new();
+        let mut stack = Vec::new();
+
+        graph.visit(config, target_task_path, &mut indices, &mut stack)?;
+
+        Ok(graph)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Checks that the target task can be given the arguments from the command line, which a task
+    /// that runs commands in parallel cannot since none of its commands is the last one
+    pub fn check_task_args(&self, task_args: &[String]) -> Result<(), WorkbenchError> {
+        let target = self
+            .nodes
+            .last()
+            .expect("the target task is always in the graph");
+
+        if !task_args.is_empty() && matches!(target.task.run, Some(Run::Parallel { .. })) {
+            return Err(WorkbenchError::ParallelTaskArgs(target.task_path.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// The part of the graph that has to run again once the tasks at the given indices have changed,
+    /// which is those tasks and every task that depends on them
+    ///
+    /// The target task depends on every other task, so it is still last unless nothing is affected.
+    pub fn affected_subgraph(&self, affected: &[usize]) -> Self {
+        let mut indices = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+
+        // Every node comes after its dependencies, so whether they are affected is already known
+        for (index, node) in self.nodes.iter().enumerate() {
+            if affected.contains(&index)
+                || node
+                    .dependencies
+                    .iter()
+                    .any(|dependency| indices[*dependency].is_some())
+            {
+                indices[index] = Some(nodes.len());
+                nodes.push(node.clone());
+            }
+        }
+
+        for node in &mut nodes {
+            // Dependencies that are not affected have already completed and do not need waiting on
+            node.dependencies = node
+                .dependencies
+                .iter()
+                .filter_map(|dependency| indices[*dependency])
+                .collect();
+
+            node.dependents = node
+                .dependents
+                .iter()
+                .filter_map(|dependent| indices[*dependent])
+                .collect();
+        }
+
+        Self { nodes }
+    }
+
+    fn visit(
+        &mut self,
+        config: &Config,
+        task_path: &TaskPath,
+        indices: &mut HashMap<TaskPath, usize>,
+        stack: &mut Vec<TaskPath>,
+    ) -> Result<usize, WorkbenchError> {
+        if let Some(index) = indices.get(task_path) {
+            return Ok(*index);
+        }
+
+        // A task that is still being visited further up the stack depends on itself
+        if let Some(position) = stack.iter().position(|item| item == task_path) {
+            let mut cycle = stack[position..].to_vec();
+            cycle.push(task_path.clone());
+            return Err(WorkbenchError::DependencyCycle(cycle));
+        }
+
+        let task = get_task_at_path(config, task_path)
+            .ok_or_else(|| WorkbenchError::TaskNotFound(task_path.clone()))?;
+
+        let mut dependencies = Vec::new();
+
+        stack.push(task_path.clone());
+
+        if let Some(ref dependency_paths) = task.dependencies {
+            for dependency_path in dependency_paths {
+                let index = self.visit(
+                    config,
+                    &TaskPath::parse(dependency_path.as_str())?,
+                    indices,
+                    stack,
+                )?;
+
+                // Listing the same dependency twice should not make the task wait on it twice
+                if !dependencies.contains(&index) {
+                    dependencies.push(index);
+                }
+            }
+        }
+
+        stack.pop();
+
+        let index = self.nodes.len();
+
+        for dependency in &dependencies {
+            self.nodes[*dependency].dependents.push(index);
+        }
+
+        self.nodes.push(Node {
+            task_path: task_path.clone(),
+            task: task.clone(),
+            directory: get_task_directory(config, task),
+            environment: get_task_environment(config, task_path, task)?,
+            dependencies,
+            dependents: Vec::new(),
+        });
+
+        indices.insert(task_path.clone(), index);
+
+        Ok(index)
+    }
+}
+
+pub fn count_dependencies_of_path(
+    config: &Config,
+    task_path: &TaskPath,
+) -> Result<u64, WorkbenchError> {
+    let graph = TaskGraph::build(config, task_path)?;
+
+    // The target task itself is the only node that is not a dependency
+    Ok(graph.nodes().len() as u64 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Command, Step};
+
+    use super::*;
+
+    fn names(graph: &TaskGraph) -> Vec<String> {
+        graph
+            .nodes()
+            .iter()
+            .map(|node| node.task_path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_not_found() {
+        let config = Config {
+            ..Default::default()
+        };
+
+        assert!(TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_build_missing_dependency() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    dependencies: Some(vec!["b".to_owned()]),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        assert!(TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_check_task_args_parallel() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Parallel {
+                        parallel: vec![Step::Command(Command::String("echo a".to_owned()))],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap();
+
+        assert!(graph.check_task_args(&[]).is_ok());
+        assert!(matches!(
+            graph.check_task_args(&["x".to_owned()]),
+            Err(WorkbenchError::ParallelTaskArgs(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_chain() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                ("a".to_owned(), Task::default()),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("c").unwrap()).unwrap();
+
+        assert_eq!(names(&graph), vec!["a", "b", "c"]);
+        assert_eq!(graph.nodes()[0].dependents, vec![1]);
+        assert_eq!(graph.nodes()[2].dependencies, vec![1]);
+    }
+
+    #[test]
+    fn test_build_shared_dependency() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                ("gen".to_owned(), Task::default()),
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned(), "gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("c").unwrap()).unwrap();
+
+        assert_eq!(names(&graph), vec!["gen", "a", "b", "c"]);
+        assert_eq!(graph.nodes()[0].dependents, vec![1, 2, 3]);
+        assert_eq!(graph.nodes()[3].dependencies, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_build_cycle() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let err = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap_err();
+
+        assert_eq!(err.to_string(), "dependency cycle detected: a -> b -> a");
+    }
+
+    #[test]
+    fn test_build_self_cycle() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    dependencies: Some(vec!["a".to_owned()]),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let err = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap_err();
+
+        assert_eq!(err.to_string(), "dependency cycle detected: a -> a");
+    }
+
+    #[test]
+    fn test_build_cycle_below_target() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["c".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let err = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap_err();
+
+        assert_eq!(err.to_string(), "dependency cycle detected: b -> c -> b");
+    }
+
+    #[test]
+    fn test_affected_subgraph() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                ("gen".to_owned(), Task::default()),
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("c").unwrap()).unwrap();
+
+        let subgraph = graph.affected_subgraph(&[2]);
+
+        assert_eq!(names(&subgraph), vec!["b", "c"]);
+        assert_eq!(subgraph.nodes()[0].dependencies, Vec::<usize>::new());
+        assert_eq!(subgraph.nodes()[0].dependents, vec![1]);
+        assert_eq!(subgraph.nodes()[1].dependencies, vec![0]);
+
+        assert_eq!(graph.affected_subgraph(&[0]), graph);
+        assert!(graph.affected_subgraph(&[]).nodes().is_empty());
+    }
+
+    #[test]
+    fn test_count_dependencies_of_path_shared_dependency() {
+        let config = Config {
+            tasks: Some(HashMap::from([
+                ("gen".to_owned(), Task::default()),
+                (
+                    "a".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["gen".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            count_dependencies_of_path(&config, &TaskPath::parse("c").unwrap()).unwrap(),
+            3
+        );
+    }
+}
@@ -14,80 +14,688 @@
 // see <https://www.gnu.org/licenses/>.
 
 use std::{
+    collections::BTreeMap,
     ffi::OsString,
+    fmt::{self, Display},
+    io::{self, IsTerminal, Read},
+    ops::Range,
+    os::unix::process::{CommandExt, ExitStatusExt},
+    path::Path,
+    process::ExitStatus,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
     time::{Duration, Instant},
 };
 
 use shell_quote::QuoteExt;
 
 use crate::{
-    config::{Run, Shell, Task},
+    config::{Command, Run, Shell, Task},
     error::WorkbenchError,
 };
 
-use super::{shell, TaskPath};
+use super::{
+    processes::{signal_process, signal_process_group, Processes, KILL_GRACE_PERIOD},
+    shell, TaskPath,
+};
+
+/// Shells report a command that was killed by a signal as having exited with this plus the signal's
+/// number
+pub const EXIT_CODE_SIGNAL_OFFSET: i32 = 128;
+
+/// Which limit a command exceeded before it was killed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timeout {
+    /// The command ran for longer than the task's `timeout`
+    Total(Duration),
+    /// The command produced no output for longer than the task's `idle_timeout`
+    Idle(Duration),
+    /// The service was not ready within its `ready.timeout`
+    Ready(Duration),
+}
+
+/// Which run of a task's command an output came from, counting from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attempt {
+    pub number: u32,
+    pub total: u32,
+}
+
+/// A signal that terminated a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signal {
+    pub number: i32,
+    pub core_dumped: bool,
+}
 
-pub const FALLBACK_EXIT_CODE_FOR_SIGNAL_TERMINATION: i32 = 255;
+impl Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.number {
+            libc::SIGHUP => "SIGHUP",
+            libc::SIGINT => "SIGINT",
+            libc::SIGQUIT => "SIGQUIT",
+            libc::SIGILL => "SIGILL",
+            libc::SIGTRAP => "SIGTRAP",
+            libc::SIGABRT => "SIGABRT",
+            libc::SIGBUS => "SIGBUS",
+            libc::SIGFPE => "SIGFPE",
+            libc::SIGKILL => "SIGKILL",
+            libc::SIGUSR1 => "SIGUSR1",
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGUSR2 => "SIGUSR2",
+            libc::SIGPIPE => "SIGPIPE",
+            libc::SIGALRM => "SIGALRM",
+            libc::SIGTERM => "SIGTERM",
+            libc::SIGXCPU => "SIGXCPU",
+            libc::SIGXFSZ => "SIGXFSZ",
+            libc::SIGSYS => "SIGSYS",
+            number => return write!(f, "signal {number}"),
+        };
 
+        write!(f, "{name}")
+    }
+}
+
+#[allow(clippy::struct_field_names)]
 #[derive(Debug, PartialEq, Eq)]
 pub struct Output {
+    /// The exit code of the command, or 128 plus the signal's number if it was killed by one
+    pub exit_code: i32,
+    pub signal: Option<Signal>,
+    pub console_output: ConsoleOutput,
+    pub duration: Duration,
+    pub timed_out: Option<Timeout>,
+    /// One entry for each step that ran, in order
+    pub steps: Vec<StepOutput>,
+}
+
+impl Output {
+    /// Whether the command exited by itself with one of the task's success exit codes
+    pub fn succeeded(&self, task: &Task) -> bool {
+        self.signal.is_none()
+            && self.timed_out.is_none()
+            && task.is_success_exit_code(self.exit_code)
+    }
+}
+
+/// How one step of a task's command went
+#[derive(Debug, PartialEq, Eq)]
+pub struct StepOutput {
     pub exit_code: i32,
-    pub console_output: Vec<u8>,
+    pub signal: Option<Signal>,
+    /// The chunks of the task's console output that came from this step
+    pub chunks: Range<usize>,
     pub duration: Duration,
+    pub timed_out: Option<Timeout>,
+}
+
+impl StepOutput {
+    /// Whether the step exited by itself with one of the task's success exit codes
+    pub fn succeeded(&self, task: &Task) -> bool {
+        self.signal.is_none()
+            && self.timed_out.is_none()
+            && task.is_success_exit_code(self.exit_code)
+    }
+}
+
+/// Which of a command's output streams some output was written to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A piece of output as it was read from a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub stream: Stream,
+    /// How long after the task started that the chunk was read
+    pub time: Duration,
+    /// Where the chunk is in its stream's output
+    pub range: Range<usize>,
+}
+
+/// Everything a task's commands wrote to stdout and stderr, kept apart
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConsoleOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Every chunk in the order it was read, so that the two streams can be shown interleaved
+    pub chunks: Vec<Chunk>,
+}
+
+impl ConsoleOutput {
+    pub fn push(&mut self, stream: Stream, time: Duration, bytes: &[u8]) {
+        let output = self.stream_mut(stream);
+        let start = output.len();
+
+        output.extend_from_slice(bytes);
+
+        let range = start..output.len();
+
+        self.chunks.push(Chunk {
+            stream,
+            time,
+            range,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The output of each chunk in `chunks`, in the order that they were read
+    pub fn interleaved(&self, chunks: Range<usize>) -> impl Iterator<Item = (Stream, &[u8])> {
+        self.chunks[chunks]
+            .iter()
+            .map(|chunk| (chunk.stream, self.bytes(chunk)))
+    }
+
+    pub fn bytes(&self, chunk: &Chunk) -> &[u8] {
+        let output = match chunk.stream {
+            Stream::Stdout => &self.stdout,
+            Stream::Stderr => &self.stderr,
+        };
+
+        &output[chunk.range.clone()]
+    }
+
+    fn stream_mut(&mut self, stream: Stream) -> &mut Vec<u8> {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+}
+
+/// Called with each line of output, without its trailing newline, when output is streamed
+pub type OnLine<'a> = &'a mut dyn FnMut(Stream, &[u8]);
+
+/// The output of a task's commands as it is read
+struct Capture<'a> {
+    start: Instant,
+    console_output: ConsoleOutput,
+    on_line: Option<OnLine<'a>>,
+}
+
+impl Capture<'_> {
+    fn push(&mut self, stream: Stream, bytes: &[u8]) {
+        let Some(on_line) = self.on_line.as_mut() else {
+            self.console_output
+                .push(stream, self.start.elapsed(), bytes);
+
+            return;
+        };
+
+        // When streaming, each line is handed off as soon as it is complete so that none of the
+        // output is kept
+        let output = self.console_output.stream_mut(stream);
+
+        output.extend_from_slice(bytes);
+
+        while let Some(position) = output.iter().position(|byte| *byte == b'\n') {
+            on_line(stream, &output[..position]);
+
+            output.drain(..=position);
+        }
+    }
+
+    /// Hands off any incomplete lines once a command has exited
+    fn finish_lines(&mut self) {
+        let Some(on_line) = self.on_line.as_mut() else {
+            return;
+        };
+
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            let output = self.console_output.stream_mut(stream);
+
+            if !output.is_empty() {
+                on_line(stream, output);
+
+                output.clear();
+            }
+        }
+    }
+}
+
+pub fn handle_execution(
+    task_path: &TaskPath,
+    task: &Task,
+    task_args: &[String],
+    directory: Option<&Path>,
+    environment: &BTreeMap<String, String>,
+    processes: &Processes,
+    on_line: Option<OnLine>,
+) -> Result<Output, WorkbenchError> {
+    assert!(
+        !matches!(task.run, Some(Run::Parallel { .. })),
+        "parallel commands are each run as a task of their own"
+    );
+
+    let steps = get_commands(task);
+
+    // Build every step up front so that a misconfigured step fails the task before anything runs
+    let expressions = steps
+        .iter()
+        .enumerate()
+        .map(|(index, (command, shell))| {
+            build_expression(
+                task_path,
+                command,
+                *shell,
+                get_step_args(task_args, index, steps.len()),
+                directory,
+                environment,
+                task.interactive == Some(true),
+            )
+        })
+        .collect::<Result<Vec<duct::Expression>, WorkbenchError>>()?;
+
+    let start = Instant::now();
+
+    // The timeout covers the whole task, while the idle timeout applies to each step on its own
+    let deadline = task
+        .timeout
+        .map(Duration::from_secs)
+        .map(|timeout| (start + timeout, Timeout::Total(timeout)));
+    let idle_timeout = task.idle_timeout.map(Duration::from_secs);
+
+    let mut capture = Capture {
+        start,
+        console_output: ConsoleOutput::default(),
+        on_line,
+    };
+    let mut step_outputs = Vec::new();
+
+    for expression in expressions {
+        let step_start = Instant::now();
+        let chunks_start = capture.console_output.chunks.len();
+
+        let (status, timed_out) = if task.interactive == Some(true) {
+            run_interactive(&expression, processes, deadline)?
+        } else {
+            run_expression(&expression, processes, deadline, idle_timeout, &mut capture)?
+        };
+
+        let (exit_code, signal) = resolve_exit_status(status);
+
+        let step_output = StepOutput {
+            exit_code,
+            signal,
+            chunks: chunks_start..capture.console_output.chunks.len(),
+            duration: step_start.elapsed(),
+            timed_out,
+        };
+
+        let succeeded = step_output.succeeded(task);
+
+        step_outputs.push(step_output);
+
+        if !succeeded || processes.is_cancelled() {
+            break;
+        }
+    }
+
+    // A task without any steps has nothing that could fail
+    let (exit_code, signal, timed_out) = step_outputs.last().map_or((0, None, None), |step| {
+        (step.exit_code, step.signal, step.timed_out)
+    });
+
+    Ok(Output {
+        exit_code,
+        signal,
+        timed_out,
+        console_output: capture.console_output,
+        duration: start.elapsed(),
+        steps: step_outputs,
+    })
+}
+
+/// Each of a task's commands along with the shell that it runs in
+pub fn get_commands(task: &Task) -> Vec<(Command, Option<&Shell>)> {
+    match &task.run {
+        None => Vec::new(),
+        Some(Run::String(command)) => {
+            vec![(Command::String(command.clone()), task.shell.as_ref())]
+        }
+        Some(Run::Args(args)) => vec![(Command::Args(args.clone()), task.shell.as_ref())],
+        Some(Run::Steps { steps } | Run::Parallel { parallel: steps }) => steps
+            .iter()
+            .map(|step| (step.command().clone(), step.shell().or(task.shell.as_ref())))
+            .collect(),
+    }
+}
+
+/// The arguments from the command line that a step is given, which only the last step of a task
+/// is, like the final command of a script
+pub const fn get_step_args(task_args: &[String], index: usize, steps: usize) -> &[String] {
+    if index + 1 == steps {
+        task_args
+    } else {
+        &[]
+    }
 }
 
-pub fn handle_execution(task_path: &TaskPath, task: &Task) -> Result<Output, WorkbenchError> {
-    let expression = match task.run {
-        Run::String(ref command) => {
-            let shell = shell::resolve(task.shell.as_ref().unwrap_or(&Shell::Bool(true)));
+/// The program and arguments that a command runs as, once its shell and the task arguments have
+/// been applied
+pub fn expand_command(
+    task_path: &TaskPath,
+    command: &Command,
+    shell: Option<&Shell>,
+    task_args: &[String],
+) -> Result<Vec<OsString>, WorkbenchError> {
+    Ok(match command {
+        Command::String(command) => {
+            let shell = shell::resolve(shell.unwrap_or(&Shell::Bool(true)));
 
             let shell = shell
                 .ok_or_else(|| WorkbenchError::ShellRequired(task_path.clone(), command.clone()))?;
 
-            duct::cmd!(shell, "-c", command)
+            if task_args.is_empty() {
+                vec![shell.into(), "-c".into(), command.into()]
+            } else {
+                // Set the positional parameters first so that the command can refer to the task
+                // arguments as $@, $1, etc.
+                let mut buffer = OsString::from("set -- ");
+
+                push_quoted_args(&mut buffer, task_args);
+                buffer.push("\n");
+                buffer.push(command);
+
+                vec![shell.into(), "-c".into(), buffer]
+            }
         }
-        Run::Args(ref args) => {
-            let shell = shell::resolve(task.shell.as_ref().unwrap_or(&Shell::Bool(false)));
+        Command::Args(args) => {
+            let shell = shell::resolve(shell.unwrap_or(&Shell::Bool(false)));
 
             match shell {
-                None => duct::cmd(args[0].as_str(), &args[1..]),
+                None => args.iter().chain(task_args).map(OsString::from).collect(),
                 Some(shell) => {
                     let mut buffer = OsString::new();
 
-                    for arg in args {
-                        buffer.push_quoted(shell_quote::Sh, arg.as_str());
-                        buffer.push(" ");
-                    }
+                    push_quoted_args(&mut buffer, args);
+                    push_quoted_args(&mut buffer, task_args);
 
-                    duct::cmd!(shell, "-c", buffer)
+                    vec![shell.into(), "-c".into(), buffer]
                 }
             }
         }
+    })
+}
+
+/// Sets up stdin for a command that is not interactive
+///
+/// The command runs in the background of the terminal, where reading from it would stop the command
+/// until it is killed, so it gets no input at all if stdin is a terminal. Input that is piped or
+/// redirected into Workbench is still passed on.
+fn background_stdin(expression: duct::Expression, stdin_is_terminal: bool) -> duct::Expression {
+    if stdin_is_terminal {
+        expression.stdin_null()
+    } else {
+        expression
+    }
+}
+
+fn build_expression(
+    task_path: &TaskPath,
+    command: &Command,
+    shell: Option<&Shell>,
+    task_args: &[String],
+    directory: Option<&Path>,
+    environment: &BTreeMap<String, String>,
+    interactive: bool,
+) -> Result<duct::Expression, WorkbenchError> {
+    let args = expand_command(task_path, command, shell, task_args)?;
+
+    let expression = duct::cmd(&args[0], &args[1..]);
+
+    let expression = match directory {
+        Some(directory) => expression.dir(directory),
+        None => expression,
     };
 
-    let start = Instant::now();
+    let expression = environment
+        .iter()
+        .fold(expression, |expression, (name, value)| {
+            expression.env(name, value)
+        });
+
+    let expression = expression.unchecked();
 
-    let output = expression
-        .unchecked()
-        .stderr_to_stdout()
-        .stdout_capture()
-        .run()?;
+    // Interactive commands inherit stdin, stdout and stderr, and stay in Workbench's process group so
+    // that they remain in the foreground of the terminal
+    if interactive {
+        return Ok(expression);
+    }
 
-    let duration = start.elapsed();
+    let expression = background_stdin(expression, io::stdin().is_terminal());
 
-    Ok(Output {
-        exit_code: output
-            .status
-            .code()
-            .unwrap_or(FALLBACK_EXIT_CODE_FOR_SIGNAL_TERMINATION),
-        console_output: output.stdout,
-        duration,
-    })
+    Ok(expression.before_spawn(|command| {
+        // Put the command in its own process group so that everything it spawns can be killed
+        // together if it times out
+        command.process_group(0);
+
+        Ok(())
+    }))
+}
+
+/// Runs a single command to completion, adding its output to `capture`
+fn run_expression(
+    expression: &duct::Expression,
+    processes: &Processes,
+    deadline: Option<(Instant, Timeout)>,
+    idle_timeout: Option<Duration>,
+    capture: &mut Capture,
+) -> Result<(ExitStatus, Option<Timeout>), WorkbenchError> {
+    let (stderr_reader, stderr_writer) = os_pipe::pipe()?;
+
+    // Our copy of the write end of the pipe is closed once the command has started, so that reading
+    // from it stops once the command exits
+    let reader = expression.stderr_file(stderr_writer).reader()?;
+
+    let process_group = i32::try_from(reader.pids()[0]).expect("process IDs fit in an i32");
+
+    processes.register(process_group);
+
+    let (activity_sender, activity_receiver) = mpsc::channel();
+    let (chunk_sender, chunk_receiver) = mpsc::channel();
+
+    let (result, timed_out) = thread::scope(|scope| {
+        let watchdog = scope.spawn(move || {
+            watch(
+                &|signal| signal_process_group(process_group, signal),
+                deadline,
+                idle_timeout,
+                &activity_receiver,
+            )
+        });
+
+        let stdout = {
+            let chunk_sender = chunk_sender.clone();
+            let reader = &reader;
+
+            scope.spawn(move || read_stream(reader, Stream::Stdout, &chunk_sender))
+        };
+
+        let stderr = scope.spawn(move || read_stream(stderr_reader, Stream::Stderr, &chunk_sender));
+
+        // The channel is closed once both streams have been read to the end
+        for (stream, bytes) in chunk_receiver {
+            // The watchdog may already have stopped if the command has no time limits
+            let _ = activity_sender.send(());
+
+            capture.push(stream, &bytes);
+        }
+
+        capture.finish_lines();
+
+        // Closing the channel tells the watchdog that the command has exited
+        drop(activity_sender);
+
+        let result = stdout
+            .join()
+            .expect("stdout reader panicked")
+            .and(stderr.join().expect("stderr reader panicked"));
+
+        (result, watchdog.join().expect("watchdog panicked"))
+    });
+
+    processes.unregister(process_group);
+
+    result?;
+
+    let status = reader
+        .try_wait()?
+        .expect("command has exited once its output is exhausted")
+        .status;
+
+    Ok((status, timed_out))
+}
+
+/// Runs a single command to completion with the terminal's stdin, stdout and stderr
+///
+/// Nothing is read from the command, so only the task's `timeout` can be enforced.
+fn run_interactive(
+    expression: &duct::Expression,
+    processes: &Processes,
+    deadline: Option<(Instant, Timeout)>,
+) -> Result<(ExitStatus, Option<Timeout>), WorkbenchError> {
+    let handle = expression.start()?;
+
+    let pid = i32::try_from(handle.pids()[0]).expect("process IDs fit in an i32");
+
+    processes.register_process(pid);
+
+    let (exit_sender, exit_receiver) = mpsc::channel();
+
+    let (result, timed_out) = thread::scope(|scope| {
+        let watchdog = scope.spawn(move || {
+            watch(
+                &|signal| signal_process(pid, signal),
+                deadline,
+                None,
+                &exit_receiver,
+            )
+        });
+
+        let result = handle.wait().map(|output| output.status);
+
+        // Closing the channel tells the watchdog that the command has exited
+        drop(exit_sender);
+
+        (result, watchdog.join().expect("watchdog panicked"))
+    });
+
+    processes.unregister_process(pid);
+
+    Ok((result?, timed_out))
+}
+
+fn resolve_exit_status(status: ExitStatus) -> (i32, Option<Signal>) {
+    match status.signal() {
+        Some(number) => (
+            EXIT_CODE_SIGNAL_OFFSET + number,
+            Some(Signal {
+                number,
+                core_dumped: status.core_dumped(),
+            }),
+        ),
+        None => (
+            status
+                .code()
+                .expect("commands that were not killed by a signal have an exit code"),
+            None,
+        ),
+    }
+}
+
+fn read_stream(
+    mut reader: impl Read,
+    stream: Stream,
+    chunks: &Sender<(Stream, Vec<u8>)>,
+) -> Result<(), io::Error> {
+    let mut buffer = [0; 4096];
+
+    loop {
+        let count = reader.read(&mut buffer)?;
+
+        if count == 0 {
+            return Ok(());
+        }
+
+        // The receiver is only dropped once both streams have been read
+        let _ = chunks.send((stream, buffer[..count].to_vec()));
+    }
+}
+
+/// Waits for the command to exit, killing it with `signal` if it exceeds either time limit
+fn watch(
+    signal: &dyn Fn(i32),
+    deadline: Option<(Instant, Timeout)>,
+    idle_timeout: Option<Duration>,
+    activity: &Receiver<()>,
+) -> Option<Timeout> {
+    let mut last_activity = Instant::now();
+
+    loop {
+        let deadline = [
+            deadline,
+            idle_timeout
+                .map(|idle_timeout| (last_activity + idle_timeout, Timeout::Idle(idle_timeout))),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(deadline, _)| *deadline);
+
+        let Some((deadline, reason)) = deadline else {
+            // Without any limits there is nothing to do but wait for the command to exit
+            while activity.recv().is_ok() {}
+
+            return None;
+        };
+
+        match activity.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(()) => last_activity = Instant::now(),
+            Err(RecvTimeoutError::Disconnected) => return None,
+            Err(RecvTimeoutError::Timeout) => {
+                terminate(signal, activity);
+
+                return Some(reason);
+            }
+        }
+    }
+}
+
+fn terminate(signal: &dyn Fn(i32), activity: &Receiver<()>) {
+    signal(libc::SIGTERM);
+
+    // A stopped command only acts on SIGTERM once it is continued
+    signal(libc::SIGCONT);
+
+    let deadline = Instant::now() + KILL_GRACE_PERIOD;
+
+    // The channel is closed once the command has exited and its output has been read
+    loop {
+        match activity.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(()) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => break,
+        }
+    }
+
+    signal(libc::SIGKILL);
+}
+
+fn push_quoted_args(buffer: &mut OsString, args: &[String]) {
+    for arg in args {
+        buffer.push_quoted(shell_quote::Sh, arg.as_str());
+        buffer.push(" ");
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::Shell;
+    use crate::config::{Shell, Step};
 
     use super::*;
 
@@ -96,20 +704,23 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["true".to_string()]),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            run: Some(Run::Args(vec!["true".to_string()])),
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, vec![]);
+        assert!(output.console_output.is_empty());
     }
 
     #[test]
@@ -117,20 +728,23 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["echo".to_string(), "a".to_string()]),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            run: Some(Run::Args(vec!["echo".to_string(), "a".to_string()])),
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, vec![b'a', b'\n']);
+        assert_eq!(output.console_output.stdout, vec![b'a', b'\n']);
     }
 
     #[test]
@@ -138,20 +752,24 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["true".to_string()]),
+            run: Some(Run::Args(vec!["true".to_string()])),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, vec![]);
+        assert!(output.console_output.is_empty());
     }
 
     #[test]
@@ -159,20 +777,24 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["echo".to_string(), "a".to_string()]),
+            run: Some(Run::Args(vec!["echo".to_string(), "a".to_string()])),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, vec![b'a', b'\n']);
+        assert_eq!(output.console_output.stdout, vec![b'a', b'\n']);
     }
 
     #[test]
@@ -180,20 +802,23 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["false".to_string()]),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            run: Some(Run::Args(vec!["false".to_string()])),
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 1);
-        assert_eq!(output.console_output, vec![]);
+        assert!(output.console_output.is_empty());
     }
 
     #[test]
@@ -201,24 +826,27 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec![
+            run: Some(Run::Args(vec![
                 "cat".to_string(),
                 "this/file/does/not/exist".to_string(),
-            ]),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ])),
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 1);
         assert_eq!(
-            output.console_output,
+            output.console_output.stderr,
             b"cat: this/file/does/not/exist: No such file or directory\n".to_vec()
         );
     }
@@ -228,20 +856,24 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["false".to_string()]),
+            run: Some(Run::Args(vec!["false".to_string()])),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 1);
-        assert_eq!(output.console_output, vec![]);
+        assert!(output.console_output.is_empty());
     }
 
     #[test]
@@ -249,47 +881,104 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec![
+            run: Some(Run::Args(vec![
                 "cat".to_string(),
                 "this/file/does/not/exist".to_string(),
-            ]),
+            ])),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 1);
         assert_eq!(
-            output.console_output,
+            output.console_output.stderr,
             b"cat: this/file/does/not/exist: No such file or directory\n".to_vec()
         );
     }
 
+    #[test]
+    fn test_handle_execution_killed_by_signal() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("kill -SEGV $$".to_owned())),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, EXIT_CODE_SIGNAL_OFFSET + libc::SIGSEGV);
+        assert_eq!(
+            output.signal.map(|signal| signal.number),
+            Some(libc::SIGSEGV)
+        );
+        assert_eq!(output.steps[0].signal, output.signal);
+    }
+
+    #[test]
+    fn test_signal_display() {
+        assert_eq!(
+            Signal {
+                number: libc::SIGKILL,
+                core_dumped: false
+            }
+            .to_string(),
+            "SIGKILL"
+        );
+        assert_eq!(
+            Signal {
+                number: 64,
+                core_dumped: false
+            }
+            .to_string(),
+            "signal 64"
+        );
+    }
+
     #[test]
     fn test_handle_execution_stdout_then_stderr() {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::String("echo a && echo b 1>&2".to_owned()),
+            run: Some(Run::String("echo a && echo b 1>&2".to_owned())),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, b"a\nb\n".to_vec());
+        assert_eq!(output.console_output.stdout, b"a\n".to_vec());
+        assert_eq!(output.console_output.stderr, b"b\n".to_vec());
     }
 
     #[test]
@@ -297,92 +986,163 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::String("echo a 1>&2 && echo b".to_owned()),
+            run: Some(Run::String("echo a 1>&2 && echo b".to_owned())),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
-        assert_eq!(output.console_output, b"a\nb\n".to_vec());
-    }
-
-    // #[test]
-    // fn test_handle_execution_args_with_spaces_without_shell() {
-    //     let task_path = TaskPath::parse("a").unwrap();
-
-    //     let task = Task {
-    //         run: Run::Args(vec![
-    //             "cat".to_owned(),
-    //             "this path does not exist".to_owned(),
-    //         ]),
-    //         shell: None,
-    //         dependencies: None,
-    //         inputs: None,
-    //         outputs: None,
-    //         description: None,
-    //         examples: None,
-    //         usage: None,
-    //     };
-
-    //     let output = handle_execution(&task_path, &task).unwrap();
-
-    //     assert_eq!(output.exit_code, 1);
-    //     assert_eq!(
-    //         output.console_output,
-    //         b"cat: this path does not exist: No such file or directory\n".to_vec()
-    //     );
-    // }
-
-    // #[test]
-    // fn test_handle_execution_args_with_spaces_with_shell() {
-    //     let task_path = TaskPath::parse("a").unwrap();
-
-    //     let task = Task {
-    //         run: Run::Args(vec![
-    //             "cat".to_owned(),
-    //             "this path does not exist".to_owned(),
-    //         ]),
-    //         shell: Some(Shell::Bool(true)),
-    //         dependencies: None,
-    //         inputs: None,
-    //         outputs: None,
-    //         description: None,
-    //         examples: None,
-    //         usage: None,
-    //     };
-
-    //     let output = handle_execution(&task_path, &task).unwrap();
-
-    //     assert_eq!(output.exit_code, 1);
-    //     assert_eq!(
-    //         output.console_output,
-    //         b"cat: this path does not exist: No such file or directory\n".to_vec()
-    //     );
-    // }
+        assert_eq!(output.console_output.stdout, b"b\n".to_vec());
+        assert_eq!(output.console_output.stderr, b"a\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_interleaved() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String(
+                "echo a && sleep 0.1 && echo b 1>&2 && sleep 0.1 && echo c".to_owned(),
+            )),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        let console_output = &output.console_output;
+
+        assert_eq!(
+            console_output
+                .interleaved(0..console_output.chunks.len())
+                .collect::<Vec<_>>(),
+            vec![
+                (Stream::Stdout, b"a\n".as_slice()),
+                (Stream::Stderr, b"b\n".as_slice()),
+                (Stream::Stdout, b"c\n".as_slice())
+            ]
+        );
+        assert!(console_output.chunks[0].time < console_output.chunks[1].time);
+    }
+
+    #[test]
+    fn test_handle_execution_args_with_spaces_without_shell() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec![
+                "cat".to_owned(),
+                "this path does not exist".to_owned(),
+            ])),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        let console_output = &output.console_output;
+
+        // The argument reaches 'cat' as a single path either way
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            console_output
+                .interleaved(0..console_output.chunks.len())
+                .filter(|(stream, _)| *stream == Stream::Stderr)
+                .flat_map(|(_, bytes)| bytes.iter().copied())
+                .collect::<Vec<_>>(),
+            b"cat: 'this path does not exist': No such file or directory\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_handle_execution_args_with_spaces_with_shell() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec![
+                "cat".to_owned(),
+                "this path does not exist".to_owned(),
+            ])),
+            shell: Some(Shell::Bool(true)),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        let console_output = &output.console_output;
+
+        // The argument reaches 'cat' as a single path either way
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(
+            console_output
+                .interleaved(0..console_output.chunks.len())
+                .filter(|(stream, _)| *stream == Stream::Stderr)
+                .flat_map(|(_, bytes)| bytes.iter().copied())
+                .collect::<Vec<_>>(),
+            b"cat: 'this path does not exist': No such file or directory\n".to_vec()
+        );
+    }
 
     #[test]
     fn test_handle_execution_string_with_shell() {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::String("set".to_owned()),
+            run: Some(Run::String("set".to_owned())),
             shell: Some(Shell::Bool(true)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        assert_eq!(handle_execution(&task_path, &task).unwrap().exit_code, 0);
+        assert_eq!(
+            handle_execution(
+                &task_path,
+                &task,
+                &[],
+                None,
+                &BTreeMap::new(),
+                &Processes::default(),
+                None
+            )
+            .unwrap()
+            .exit_code,
+            0
+        );
     }
 
     #[test]
@@ -390,18 +1150,23 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec!["echo".to_owned(), "$SHELL".to_owned()]),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            run: Some(Run::Args(vec!["echo".to_owned(), "$SHELL".to_owned()])),
+            ..Default::default()
         };
 
         assert_eq!(
-            handle_execution(&task_path, &task).unwrap().console_output,
+            handle_execution(
+                &task_path,
+                &task,
+                &[],
+                None,
+                &BTreeMap::new(),
+                &Processes::default(),
+                None
+            )
+            .unwrap()
+            .console_output
+            .stdout,
             b"$SHELL\n".to_vec()
         );
     }
@@ -411,17 +1176,21 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::Args(vec![":".to_owned()]),
+            run: Some(Run::Args(vec![":".to_owned()])),
             shell: Some(Shell::String("/bin/sh".to_owned())),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        let output = handle_execution(&task_path, &task).unwrap();
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(output.exit_code, 0);
         assert!(output.console_output.is_empty());
@@ -432,17 +1201,24 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::String("set".to_owned()),
-            shell: None,
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            run: Some(Run::String("set".to_owned())),
+            ..Default::default()
         };
 
-        assert_eq!(handle_execution(&task_path, &task).unwrap().exit_code, 0);
+        assert_eq!(
+            handle_execution(
+                &task_path,
+                &task,
+                &[],
+                None,
+                &BTreeMap::new(),
+                &Processes::default(),
+                None
+            )
+            .unwrap()
+            .exit_code,
+            0
+        );
     }
 
     #[test]
@@ -450,16 +1226,721 @@ mod tests {
         let task_path = TaskPath::parse("a").unwrap();
 
         let task = Task {
-            run: Run::String("set".to_owned()),
+            run: Some(Run::String("set".to_owned())),
             shell: Some(Shell::Bool(false)),
-            dependencies: None,
-            inputs: None,
-            outputs: None,
-            description: None,
-            examples: None,
-            usage: None,
+            ..Default::default()
         };
 
-        assert!(handle_execution(&task_path, &task).is_err());
+        assert!(handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_handle_execution_args_with_task_args_without_shell() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec!["echo".to_owned(), "a".to_owned()])),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &["--filter".to_owned(), "$b c".to_owned()],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a --filter $b c\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_args_with_task_args_with_shell() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec!["echo".to_owned(), "a".to_owned()])),
+            shell: Some(Shell::Bool(true)),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &["--filter".to_owned(), "$b c".to_owned()],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a --filter $b c\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_string_with_task_args() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("echo $# \"$1\" && echo \"$@\"".to_owned())),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &["it's $b".to_owned(), "; false".to_owned()],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(
+            output.console_output.stdout,
+            b"2 it's $b\nit's $b ; false\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_handle_execution_stream_lines() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String(
+                "echo a && sleep 0.1 && echo b 1>&2 && exit 3".to_owned(),
+            )),
+            ..Default::default()
+        };
+
+        let mut lines = Vec::new();
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            Some(&mut |stream, line: &[u8]| lines.push((stream, line.to_vec()))),
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 3);
+        assert!(output.console_output.is_empty());
+        assert_eq!(
+            lines,
+            vec![
+                (Stream::Stdout, b"a".to_vec()),
+                (Stream::Stderr, b"b".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handle_execution_timeout() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("echo a && sleep 10".to_owned())),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert_eq!(output.console_output.stdout, b"a\n".to_vec());
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_handle_execution_idle_timeout() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String(
+                "echo a && sleep 0.6 && echo b && sleep 0.6 && echo c && sleep 10".to_owned(),
+            )),
+            idle_timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Idle(Duration::from_secs(1)))
+        );
+        assert_eq!(output.console_output.stdout, b"a\nb\nc\n".to_vec());
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_handle_execution_timeout_kills_process_group() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("sleep 10 & sleep 10 & wait".to_owned())),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        // The background processes hold the output open, so this only returns once they are killed
+        // too
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_background_stdin_terminal() {
+        let expression = duct::cmd!("sh", "-c", "read line && echo $line");
+
+        // Input given around the command does not reach it, and reading gets the end of the input
+        // straight away instead of waiting on the terminal
+        let output = background_stdin(expression, true)
+            .stdin_bytes("data\n")
+            .stdout_capture()
+            .unchecked()
+            .run()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_background_stdin_piped() {
+        let expression = duct::cmd!("sh", "-c", "read line && echo $line");
+
+        let output = background_stdin(expression, false)
+            .stdin_bytes("data\n")
+            .stdout_capture()
+            .run()
+            .unwrap();
+
+        assert_eq!(output.stdout, b"data\n");
+    }
+
+    #[test]
+    fn test_handle_execution_timeout_continues_stopped_command() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("kill -STOP $$ && sleep 10".to_owned())),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        // The command acts on SIGTERM rather than waiting to be killed once the grace period is over
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert!(output.duration < KILL_GRACE_PERIOD);
+    }
+
+    #[test]
+    fn test_handle_execution_timeout_escalates_to_sigkill() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("trap '' TERM && sleep 10".to_owned())),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert_eq!(output.exit_code, EXIT_CODE_SIGNAL_OFFSET + libc::SIGKILL);
+        assert_eq!(
+            output.signal,
+            Some(Signal {
+                number: libc::SIGKILL,
+                core_dumped: false
+            })
+        );
+        assert!(output.duration >= KILL_GRACE_PERIOD);
+    }
+
+    #[test]
+    fn test_handle_execution_within_timeout() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("echo a".to_owned())),
+            timeout: Some(10),
+            idle_timeout: Some(10),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.timed_out, None);
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_cancelled() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("sleep 10; echo a".to_owned())),
+            ..Default::default()
+        };
+
+        let processes = Processes::default();
+
+        let output = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(200));
+
+                processes.cancel(libc::SIGINT);
+            });
+
+            handle_execution(
+                &task_path,
+                &task,
+                &[],
+                None,
+                &BTreeMap::new(),
+                &processes,
+                None,
+            )
+            .unwrap()
+        });
+
+        assert_ne!(output.exit_code, 0);
+        assert!(output.console_output.is_empty());
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_handle_execution_interactive_cancelled() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec!["sleep".to_owned(), "10".to_owned()])),
+            interactive: Some(true),
+            ..Default::default()
+        };
+
+        let processes = Processes::default();
+
+        let output = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(200));
+
+                processes.cancel(libc::SIGTERM);
+            });
+
+            handle_execution(
+                &task_path,
+                &task,
+                &[],
+                None,
+                &BTreeMap::new(),
+                &processes,
+                None,
+            )
+            .unwrap()
+        });
+
+        assert_eq!(
+            output.signal,
+            Some(Signal {
+                number: libc::SIGTERM,
+                core_dumped: false
+            })
+        );
+        assert!(output.duration < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_handle_execution_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Args(vec!["ls".to_owned()])),
+            ..Default::default()
+        };
+
+        std::fs::write(temp_dir.path().join("a.txt"), "").unwrap();
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            Some(temp_dir.path()),
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a.txt\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_environment() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("echo \"$A $B\"".to_owned())),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::from([
+                ("A".to_owned(), "a".to_owned()),
+                ("B".to_owned(), "b c".to_owned()),
+            ]),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a b c\n".to_vec());
+    }
+
+    #[test]
+    fn test_handle_execution_steps() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("echo a".to_owned())),
+                    Step::Command(Command::Args(vec!["echo".to_owned(), "b".to_owned()])),
+                ],
+            }),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a\nb\n");
+        assert_eq!(output.steps.len(), 2);
+        assert_eq!(output.steps[0].chunks, 0..1);
+        assert_eq!(output.steps[1].chunks, 1..2);
+    }
+
+    #[test]
+    fn test_handle_execution_steps_with_task_args() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("echo a".to_owned())),
+                    Step::Command(Command::Args(vec!["echo".to_owned(), "b".to_owned()])),
+                ],
+            }),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &["c".to_owned()],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a\nb c\n");
+    }
+
+    #[test]
+    fn test_handle_execution_steps_stop_at_first_failure() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("echo a".to_owned())),
+                    Step::Command(Command::String("exit 3".to_owned())),
+                    Step::Command(Command::String("echo c".to_owned())),
+                ],
+            }),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.console_output.stdout, b"a\n");
+        assert_eq!(output.steps.len(), 2);
+        assert_eq!(output.steps[0].exit_code, 0);
+        assert_eq!(output.steps[1].exit_code, 3);
+    }
+
+    #[test]
+    fn test_handle_execution_steps_success_exit_codes() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("exit 1".to_owned())),
+                    Step::Command(Command::String("echo b".to_owned())),
+                ],
+            }),
+            success_exit_codes: Some(vec![0, 1]),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert!(output.succeeded(&task));
+        assert_eq!(output.console_output.stdout, b"b\n");
+        assert_eq!(output.steps.len(), 2);
+    }
+
+    #[test]
+    fn test_handle_execution_steps_with_shell() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::Args(vec!["echo".to_owned(), "a".to_owned()])),
+                    Step::Object {
+                        run: Command::String("echo b | tr b c".to_owned()),
+                        shell: Some(Shell::Bool(true)),
+                    },
+                ],
+            }),
+            shell: Some(Shell::Bool(false)),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.console_output.stdout, b"a\nc\n");
+    }
+
+    #[test]
+    fn test_handle_execution_steps_share_timeout() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::Steps {
+                steps: vec![
+                    Step::Command(Command::String("sleep 0.6".to_owned())),
+                    Step::Command(Command::String("sleep 0.6".to_owned())),
+                ],
+            }),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert_eq!(output.steps.len(), 2);
+        assert_eq!(output.steps[0].timed_out, None);
+    }
+
+    #[test]
+    fn test_handle_execution_interactive() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("echo a; exit 3".to_owned())),
+            interactive: Some(true),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        // The output goes straight to the terminal rather than being captured
+        assert_eq!(output.exit_code, 3);
+        assert!(output.console_output.is_empty());
+    }
+
+    #[test]
+    fn test_handle_execution_interactive_timeout() {
+        let task_path = TaskPath::parse("a").unwrap();
+
+        let task = Task {
+            run: Some(Run::String("sleep 10".to_owned())),
+            interactive: Some(true),
+            timeout: Some(1),
+            ..Default::default()
+        };
+
+        let output = handle_execution(
+            &task_path,
+            &task,
+            &[],
+            None,
+            &BTreeMap::new(),
+            &Processes::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.timed_out,
+            Some(Timeout::Total(Duration::from_secs(1)))
+        );
+        assert!(output.duration < Duration::from_secs(5));
     }
 }
@@ -0,0 +1,88 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{num::NonZeroUsize, sync::Arc, thread};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Slots shared by every task in an invocation, limiting how many commands run at once
+#[derive(Clone, Debug)]
+pub struct Jobs {
+    semaphore: Arc<Semaphore>,
+    total: usize,
+}
+
+impl Jobs {
+    pub fn new(requested: u32) -> Self {
+        // Zero means one slot per available CPU
+        let total = if requested == 0 {
+            thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        } else {
+            requested as usize
+        };
+
+        Self {
+            semaphore: Arc::new(Semaphore::new(total)),
+            total,
+        }
+    }
+
+    // The slot is released when the returned permit is dropped
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("job semaphore is never closed")
+    }
+
+    pub const fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.total - self.semaphore.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_new_explicit() {
+        assert_eq!(Jobs::new(3).total(), 3);
+    }
+
+    #[test]
+    fn test_jobs_new_zero_uses_cpus() {
+        assert!(Jobs::new(0).total() >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_in_use() {
+        let jobs = Jobs::new(2);
+
+        assert_eq!(jobs.in_use(), 0);
+
+        let permit = jobs.acquire().await;
+
+        assert_eq!(jobs.in_use(), 1);
+
+        drop(permit);
+
+        assert_eq!(jobs.in_use(), 0);
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{ffi::OsString, path::PathBuf};
+
+use crate::{config::Run, error::WorkbenchError};
+
+use super::{conditions, handlers, should_run_task, Node, TaskGraph, TaskPath};
+
+/// What a task would do if the invocation were run for real
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlannedTask {
+    pub task_path: TaskPath,
+    /// Why the task would be skipped, or `None` if it would run
+    pub skip_reason: Option<String>,
+    /// The `when` command that would decide whether the task runs, which a dry run cannot know
+    pub probe: Option<Vec<OsString>>,
+    /// The commands that the task would run, each as the program and its arguments
+    pub commands: Vec<Vec<OsString>>,
+    /// The `finally` command that would run once the task's commands have finished
+    pub finally: Option<Vec<OsString>>,
+    /// Whether the commands would run at the same time rather than one after another
+    pub parallel: bool,
+    pub directory: Option<PathBuf>,
+}
+
+/// Works out what each task in the graph would do, in the order that they would run in, without
+/// running anything
+///
+/// Whether a task would be skipped is decided from the files and environment as they are now, so a
+/// task whose inputs would be changed by one of its dependencies may be reported as cached even
+/// though it would run.
+pub fn plan(graph: &TaskGraph, task_args: &[String]) -> Result<Vec<PlannedTask>, WorkbenchError> {
+    let nodes = graph.nodes();
+
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            // Arguments from the command line are only meant for the target task, which is always
+            // the last node
+            let task_args = if index == nodes.len() - 1 {
+                task_args
+            } else {
+                &[]
+            };
+
+            plan_task(node, task_args)
+        })
+        .collect()
+}
+
+fn plan_task(node: &Node, task_args: &[String]) -> Result<PlannedTask, WorkbenchError> {
+    let task = &node.task;
+    let directory = node.directory.as_deref();
+
+    let mut skip_reason = conditions::check_static_conditions(task, directory, &node.environment);
+
+    // Tasks without a command are never cached
+    if skip_reason.is_none() && task.run.is_some() {
+        skip_reason = should_run_task(task, directory)?;
+    }
+
+    let probe = task
+        .when
+        .as_ref()
+        .and_then(|when| when.run.as_ref())
+        .map(|command| handlers::expand_command(&node.task_path, command, task.shell.as_ref(), &[]))
+        .transpose()?;
+
+    let steps = handlers::get_commands(task);
+
+    let commands = steps
+        .iter()
+        .enumerate()
+        .map(|(index, (command, shell))| {
+            let step_args = handlers::get_step_args(task_args, index, steps.len());
+
+            handlers::expand_command(&node.task_path, command, *shell, step_args)
+        })
+        .collect::<Result<Vec<_>, WorkbenchError>>()?;
+
+    let finally = task
+        .finally
+        .as_ref()
+        .map(|command| handlers::expand_command(&node.task_path, command, task.shell.as_ref(), &[]))
+        .transpose()?;
+
+    Ok(PlannedTask {
+        task_path: node.task_path.clone(),
+        skip_reason,
+        probe,
+        commands,
+        finally,
+        parallel: matches!(task.run, Some(Run::Parallel { .. })),
+        directory: node.directory.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use crate::config::{Command, Config, Files, Step, Task};
+
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(temp_dir.path().join("input.txt"), "").unwrap();
+        fs::write(temp_dir.path().join("output.txt"), "").unwrap();
+
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["touch".to_owned()])),
+                        inputs: Some(Files::List(vec!["input.txt".to_owned()])),
+                        outputs: Some(Files::List(vec!["output.txt".to_owned()])),
+                        finally: Some(Command::Args(vec!["rm".to_owned(), "lock".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::Args(vec!["echo".to_owned()])),
+                        dependencies: Some(vec!["a".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            directory: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("c").unwrap()).unwrap();
+
+        let planned_tasks = plan(&graph, &["x".to_owned()]).unwrap();
+
+        assert_eq!(
+            planned_tasks,
+            vec![
+                PlannedTask {
+                    task_path: TaskPath::parse("a").unwrap(),
+                    skip_reason: Some("cached".to_owned()),
+                    probe: None,
+                    commands: vec![vec!["touch".into()]],
+                    finally: Some(vec!["rm".into(), "lock".into()]),
+                    parallel: false,
+                    directory: Some(temp_dir.path().to_path_buf()),
+                },
+                PlannedTask {
+                    task_path: TaskPath::parse("b").unwrap(),
+                    skip_reason: None,
+                    probe: None,
+                    // Arguments from the command line only go to the target task
+                    commands: vec![vec!["echo".into()]],
+                    finally: None,
+                    parallel: false,
+                    directory: Some(temp_dir.path().to_path_buf()),
+                },
+                PlannedTask {
+                    task_path: TaskPath::parse("c").unwrap(),
+                    skip_reason: None,
+                    probe: None,
+                    commands: Vec::new(),
+                    finally: None,
+                    parallel: false,
+                    directory: Some(temp_dir.path().to_path_buf()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_task_args() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Args(vec!["echo".to_owned()])),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap();
+
+        let planned_tasks = plan(&graph, &["x".to_owned()]).unwrap();
+
+        assert_eq!(
+            planned_tasks[0].commands,
+            vec![vec![OsString::from("echo"), OsString::from("x")]]
+        );
+    }
+
+    #[test]
+    fn test_plan_steps_task_args() {
+        let config = Config {
+            tasks: Some(HashMap::from([(
+                "a".to_owned(),
+                Task {
+                    run: Some(Run::Steps {
+                        steps: vec![
+                            Step::Command(Command::Args(vec!["echo".to_owned()])),
+                            Step::Command(Command::Args(vec!["ls".to_owned()])),
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            )])),
+            ..Default::default()
+        };
+
+        let graph = TaskGraph::build(&config, &TaskPath::parse("a").unwrap()).unwrap();
+
+        let planned_tasks = plan(&graph, &["x".to_owned()]).unwrap();
+
+        assert_eq!(
+            planned_tasks[0].commands,
+            vec![
+                vec![OsString::from("echo")],
+                vec![OsString::from("ls"), OsString::from("x")]
+            ]
+        );
+    }
+}
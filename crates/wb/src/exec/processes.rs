@@ -0,0 +1,301 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::sync::{Notify, OwnedMutexGuard};
+
+/// How long a command is given to exit after being signalled before it is sent SIGKILL
+pub const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Every command running in an invocation, so that they can all be signalled when it is cancelled
+#[derive(Clone, Debug, Default)]
+pub struct Processes {
+    targets: Arc<Mutex<BTreeSet<Target>>>,
+    cancelled: Arc<AtomicBool>,
+    /// Whether a task failing stops the tasks that have not started yet from starting
+    stop_on_failure: Arc<AtomicBool>,
+    /// Whether tasks that have not started yet should not be started, while letting the ones that
+    /// are running finish
+    starting_stopped: Arc<AtomicBool>,
+    /// Wakes everything waiting for the invocation to be cancelled
+    cancellation: Arc<Notify>,
+    terminal: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// A running command that is signalled when the invocation is cancelled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Target {
+    /// A command in its own process group, which is signalled along with everything it started
+    ProcessGroup(i32),
+    /// An interactive command, which shares Workbench's process group so is signalled on its own
+    Process(i32),
+}
+
+impl Target {
+    fn signal(self, signal: i32) {
+        match self {
+            Self::ProcessGroup(process_group) => signal_process_group(process_group, signal),
+            Self::Process(pid) => signal_process(pid, signal),
+        }
+    }
+}
+
+impl Processes {
+    pub fn register(&self, process_group: i32) {
+        self.add(Target::ProcessGroup(process_group));
+    }
+
+    pub fn unregister(&self, process_group: i32) {
+        self.remove(Target::ProcessGroup(process_group));
+    }
+
+    /// Registers an interactive command, which is in Workbench's process group rather than its own
+    pub fn register_process(&self, pid: i32) {
+        self.add(Target::Process(pid));
+    }
+
+    pub fn unregister_process(&self, pid: i32) {
+        self.remove(Target::Process(pid));
+    }
+
+    fn add(&self, target: Target) {
+        let mut targets = self.targets.lock().expect("process mutex is poisoned");
+
+        targets.insert(target);
+
+        // A command that was spawned just as the invocation was cancelled would otherwise be missed
+        if self.is_cancelled() {
+            target.signal(libc::SIGTERM);
+        }
+    }
+
+    fn remove(&self, target: Target) {
+        self.targets
+            .lock()
+            .expect("process mutex is poisoned")
+            .remove(&target);
+    }
+
+    /// Forwards a signal to every running command and stops any new ones from starting
+    pub fn cancel(&self, signal: i32) {
+        let targets = self.targets.lock().expect("process mutex is poisoned");
+
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        for target in targets.iter() {
+            target.signal(signal);
+
+            // A stopped command only acts on the signal once it is continued
+            target.signal(libc::SIGCONT);
+        }
+
+        self.cancellation.notify_waiters();
+    }
+
+    /// Kills every command that is still running
+    pub fn kill(&self) {
+        for target in self
+            .targets
+            .lock()
+            .expect("process mutex is poisoned")
+            .iter()
+        {
+            target.signal(libc::SIGKILL);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Makes the first task that fails stop any task that has not started yet from starting, without
+    /// signalling the ones that are running
+    pub fn stop_starting_on_failure(&self) {
+        self.stop_on_failure.store(true, Ordering::SeqCst);
+    }
+
+    /// Records that a task failed, which stops new tasks from starting if it should
+    pub fn task_failed(&self) {
+        if self.stop_on_failure.load(Ordering::SeqCst) {
+            self.starting_stopped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether new tasks should not be started, either because the invocation was cancelled or
+    /// because a task failed
+    pub fn is_starting_stopped(&self) -> bool {
+        self.is_cancelled() || self.starting_stopped.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the invocation is cancelled, returning straight away if it already has been
+    pub async fn cancelled(&self) {
+        let notified = self.cancellation.notified();
+
+        tokio::pin!(notified);
+
+        // Register for the notification before checking, so that a cancellation in between the two
+        // is not missed
+        notified.as_mut().enable();
+
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+
+    /// Waits until no interactive command is using the terminal, then holds it until the returned
+    /// guard is dropped
+    pub async fn lock_terminal(&self) -> OwnedMutexGuard<()> {
+        self.terminal.clone().lock_owned().await
+    }
+
+    /// Whether an interactive command is currently using the terminal
+    pub fn is_terminal_locked(&self) -> bool {
+        self.terminal.try_lock().is_err()
+    }
+}
+
+pub fn signal_process(pid: i32, signal: i32) {
+    // SAFETY: kill has no memory safety requirements
+    unsafe {
+        libc::kill(pid, signal);
+    }
+}
+
+pub fn signal_process_group(process_group: i32, signal: i32) {
+    // SAFETY: kill has no memory safety requirements, and a negative PID targets only the process
+    // group that was created for the command
+    unsafe {
+        libc::kill(-process_group, signal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use std::os::unix::process::CommandExt;
+
+    use super::*;
+
+    #[test]
+    fn test_processes_cancel() {
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        let process_group = i32::try_from(child.id()).unwrap();
+
+        let processes = Processes::default();
+
+        processes.register(process_group);
+
+        assert!(!processes.is_cancelled());
+
+        processes.cancel(libc::SIGTERM);
+
+        assert!(processes.is_cancelled());
+        assert!(!child.wait().unwrap().success());
+
+        processes.unregister(process_group);
+    }
+
+    #[test]
+    fn test_processes_cancel_process() {
+        // Interactive commands stay in Workbench's process group, so only the command is signalled
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+
+        let pid = i32::try_from(child.id()).unwrap();
+
+        let processes = Processes::default();
+
+        processes.register_process(pid);
+        processes.cancel(libc::SIGTERM);
+
+        assert!(!child.wait().unwrap().success());
+
+        processes.unregister_process(pid);
+    }
+
+    #[test]
+    fn test_processes_kill_process() {
+        // Ignores SIGTERM, so only the kill stops it
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; while true; do sleep 0.1; done"])
+            .spawn()
+            .unwrap();
+
+        let pid = i32::try_from(child.id()).unwrap();
+
+        // Gives the shell time to set up the trap
+        std::thread::sleep(Duration::from_millis(200));
+
+        let processes = Processes::default();
+
+        processes.register_process(pid);
+        processes.cancel(libc::SIGTERM);
+
+        std::thread::sleep(Duration::from_millis(200));
+
+        assert!(child.try_wait().unwrap().is_none());
+
+        processes.kill();
+
+        assert!(!child.wait().unwrap().success());
+
+        processes.unregister_process(pid);
+    }
+
+    #[tokio::test]
+    async fn test_processes_cancelled() {
+        let processes = Processes::default();
+
+        let waiting = tokio::spawn({
+            let processes = processes.clone();
+            async move { processes.cancelled().await }
+        });
+
+        processes.cancel(libc::SIGTERM);
+
+        waiting.await.unwrap();
+
+        // Waiting once the invocation has already been cancelled returns straight away
+        processes.cancelled().await;
+    }
+
+    #[tokio::test]
+    async fn test_processes_lock_terminal() {
+        let processes = Processes::default();
+
+        assert!(!processes.is_terminal_locked());
+
+        let terminal = processes.lock_terminal().await;
+
+        assert!(processes.is_terminal_locked());
+
+        drop(terminal);
+
+        assert!(!processes.is_terminal_locked());
+    }
+}
@@ -0,0 +1,545 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use tokio::{
+    net::TcpStream,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        Notify,
+    },
+    task::{self, JoinHandle},
+    time,
+};
+
+use crate::{
+    config::{Ready, Run, Task},
+    console::{Level, Log, OutputMode, Progress},
+    error::WorkbenchError,
+};
+
+use super::{
+    check_conditions, conditions, environment, get_outcome, handlers, processes, run_finally,
+    Attempt, ConsoleOutput, Jobs, Node, Outcome, Output, Processes, Stream, TaskGraph, Timeout,
+};
+
+/// How long to wait between checking whether a service is ready
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Services only ever run once
+const ATTEMPT: Attempt = Attempt {
+    number: 1,
+    total: 1,
+};
+
+/// The service tasks that an invocation has started, so that each one can be stopped once nothing
+/// needs it anymore
+pub struct Services {
+    handles: HashMap<usize, ServiceHandle>,
+    /// Services that have started but are not ready yet
+    starting: BTreeSet<usize>,
+    /// The number of tasks depending on each task that have not finished yet
+    users: Vec<usize>,
+    /// A service that is the target task has nothing depending on it, so it runs until it exits
+    target: usize,
+    ready: UnboundedSender<usize>,
+}
+
+impl Services {
+    /// Creates the services for a graph, along with the channel that each one sends its index on
+    /// once the tasks that depend on it can start
+    pub fn new(graph: &TaskGraph) -> (Self, UnboundedReceiver<usize>) {
+        let (ready, ready_receiver) = mpsc::unbounded_channel();
+
+        let services = Self {
+            handles: HashMap::new(),
+            starting: BTreeSet::new(),
+            users: graph
+                .nodes()
+                .iter()
+                .map(|node| node.dependents.len())
+                .collect(),
+            target: graph.nodes().len().saturating_sub(1),
+            ready,
+        };
+
+        (services, ready_receiver)
+    }
+
+    /// Registers a service that is about to start, returning the handle that it runs with
+    pub fn start(&mut self, index: usize) -> ServiceHandle {
+        let handle = ServiceHandle {
+            index,
+            ready: self.ready.clone(),
+            stop: Arc::default(),
+        };
+
+        self.handles.insert(index, handle.clone());
+        self.starting.insert(index);
+
+        handle
+    }
+
+    /// Marks a service as ready for the tasks that depend on it
+    pub fn ready(&mut self, index: usize) {
+        self.starting.remove(&index);
+    }
+
+    /// Marks a task as finished, stopping any services that it was the last task to need
+    pub fn finish(&mut self, node: &Node, index: usize) {
+        self.starting.remove(&index);
+        self.handles.remove(&index);
+
+        for dependency in &node.dependencies {
+            self.users[*dependency] -= 1;
+
+            if self.users[*dependency] == 0 {
+                if let Some(handle) = self.handles.get(dependency) {
+                    handle.stop();
+                }
+            }
+        }
+    }
+
+    /// Stops every service once the invocation is cancelled, or once services are all that is left
+    /// running and so nothing else could start that needs them
+    pub fn stop_unused(&self, running: usize, cancelled: bool) {
+        let idle = running == self.handles.len() && self.starting.is_empty();
+
+        for (index, handle) in &self.handles {
+            if cancelled || (idle && *index != self.target) {
+                handle.stop();
+            }
+        }
+    }
+}
+
+/// How a service tells the invocation that it is ready, and how the invocation tells it to stop
+#[derive(Clone)]
+pub struct ServiceHandle {
+    index: usize,
+    ready: UnboundedSender<usize>,
+    stop: Arc<Notify>,
+}
+
+impl ServiceHandle {
+    fn ready(&self) {
+        // The receiver is only dropped once every service has finished
+        let _ = self.ready.send(self.index);
+    }
+
+    fn stop(&self) {
+        self.stop.notify_one();
+    }
+}
+
+/// A service's command running in the background
+struct Service {
+    /// The service's own processes, so that it can be stopped without cancelling the invocation
+    processes: Processes,
+    exited: JoinHandle<Result<Output, WorkbenchError>>,
+    /// Whether a line of output has matched `ready.log`, which is true if there is no pattern
+    log_matched: Arc<AtomicBool>,
+    /// The service's output, kept here when it is grouped since it has to be checked line by line
+    console_output: Arc<Mutex<ConsoleOutput>>,
+    start: Instant,
+}
+
+/// How a service's wait to be ready ended
+#[derive(Debug, PartialEq, Eq)]
+enum Readiness {
+    Ready,
+    Exited,
+    TimedOut(Duration),
+    Cancelled,
+}
+
+/// Runs a service task, telling the invocation that it is ready once all of its readiness checks
+/// pass, and keeps it running until it is stopped or exits by itself
+///
+/// Services do not take up a job slot, since the tasks that depend on them could never start
+/// otherwise. A service that exits by itself fails unless it exits with a success exit code, and
+/// one that is stopped always succeeds. Its `finally` command runs once it has stopped or exited.
+pub async fn run<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    console_context: &ConsoleContext,
+    jobs: &Jobs,
+    processes: &Processes,
+    node: &Node,
+    task_args: &[String],
+    output_mode: OutputMode,
+    handle: ServiceHandle,
+) -> Result<Outcome, WorkbenchError> {
+    let task_path = &node.task_path;
+    let task = &node.task;
+
+    if processes.is_starting_stopped() {
+        return Ok(Outcome::Failed);
+    }
+
+    console_context.begin_task(task_path, task);
+
+    if let Some(reason) = check_conditions(node, processes).await? {
+        console_context.log_exec_skipped(task_path, reason);
+        console_context.complete_task();
+
+        handle.ready();
+
+        return Ok(Outcome::Succeeded);
+    }
+
+    let ready = task.ready.clone().unwrap_or_default();
+
+    let service = Service::start(
+        console_context,
+        jobs,
+        node,
+        task_args,
+        output_mode,
+        ready.log.as_deref().map(Regex::new).transpose()?,
+    )?;
+
+    let outcome = supervise(console_context, processes, node, service, &ready, &handle).await;
+
+    // The cleanup command runs however the service ended
+    let cleanup = run_finally(console_context, jobs, node, output_mode).await;
+
+    Ok(outcome?.combine(allow_failure(console_context, node, cleanup?)))
+}
+
+/// Waits for a service that has started to be ready, and then keeps it running until it is stopped
+/// or exits by itself
+async fn supervise(
+    console_context: &(impl Log + Progress),
+    processes: &Processes,
+    node: &Node,
+    mut service: Service,
+    ready: &Ready,
+    handle: &ServiceHandle,
+) -> Result<Outcome, WorkbenchError> {
+    let task_path = &node.task_path;
+    let task = &node.task;
+
+    let readiness = match wait_until_ready(&service, node, ready, processes).await {
+        Ok(readiness) => readiness,
+        Err(err) => {
+            service.stop().await?;
+
+            return Err(err);
+        }
+    };
+
+    let outcome = match readiness {
+        Readiness::Ready => {
+            console_context.log_service_ready(task_path, task, service.start.elapsed());
+            console_context.complete_task();
+
+            handle.ready();
+
+            return keep_running(console_context, node, service, handle).await;
+        }
+        Readiness::Exited => {
+            let result = (&mut service.exited).await;
+            let output = service.finish(result??);
+
+            console_context.log_exec_output(task_path, task, &output, ATTEMPT);
+            console_context.log_message(
+                Level::Error,
+                format!("'{task_path}' exited before it was ready"),
+            );
+
+            match get_outcome(task, &output) {
+                Outcome::Killed(signal) => Outcome::Killed(signal),
+                _ => Outcome::Failed,
+            }
+        }
+        Readiness::TimedOut(timeout) => {
+            let output = service.stop().await?;
+
+            console_context.log_exec_timed_out(
+                task_path,
+                task,
+                &output,
+                Timeout::Ready(timeout),
+                ATTEMPT,
+            );
+
+            Outcome::Failed
+        }
+        Readiness::Cancelled => {
+            let output = service.stop().await?;
+
+            console_context.log_exec_cancelled(task_path, task, &output);
+            console_context.complete_task();
+
+            return Ok(Outcome::Failed);
+        }
+    };
+
+    let outcome = allow_failure(console_context, node, outcome);
+
+    console_context.complete_task();
+
+    // The tasks that depend on a service that is allowed to fail still run without it
+    if outcome.succeeded() {
+        handle.ready();
+    }
+
+    Ok(outcome)
+}
+
+/// Keeps a ready service running until it is stopped or exits by itself
+async fn keep_running(
+    console_context: &impl Log,
+    node: &Node,
+    mut service: Service,
+    handle: &ServiceHandle,
+) -> Result<Outcome, WorkbenchError> {
+    let exited = tokio::select! {
+        result = &mut service.exited => Some(result),
+        () = handle.stop.notified() => None,
+    };
+
+    let Some(result) = exited else {
+        let output = service.stop().await?;
+
+        console_context.log_service_stopped(&node.task_path, &node.task, &output);
+
+        return Ok(Outcome::Succeeded);
+    };
+
+    let output = service.finish(result??);
+
+    console_context.log_exec_output(&node.task_path, &node.task, &output, ATTEMPT);
+
+    Ok(allow_failure(
+        console_context,
+        node,
+        get_outcome(&node.task, &output),
+    ))
+}
+
+fn allow_failure(console_context: &impl Log, node: &Node, outcome: Outcome) -> Outcome {
+    if !outcome.succeeded() && node.task.allow_failure == Some(true) {
+        console_context.log_exec_allowed_failure(&node.task_path);
+
+        return Outcome::AllowedFailure;
+    }
+
+    outcome
+}
+
+impl Service {
+    fn start<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+        console_context: &ConsoleContext,
+        jobs: &Jobs,
+        node: &Node,
+        task_args: &[String],
+        output_mode: OutputMode,
+        log_pattern: Option<Regex>,
+    ) -> Result<Self, WorkbenchError> {
+        if !matches!(node.task.run, Some(Run::String(_) | Run::Args(_))) {
+            return Err(WorkbenchError::ServiceCommand(node.task_path.clone()));
+        }
+
+        let mut environment = node.environment.clone();
+
+        environment::extend_run_environment(
+            &mut environment,
+            &node.task,
+            node.directory.as_deref(),
+            jobs.total(),
+        )?;
+
+        let processes = Processes::default();
+        let log_matched = Arc::new(AtomicBool::new(log_pattern.is_none()));
+        let console_output = Arc::<Mutex<ConsoleOutput>>::default();
+        let start = Instant::now();
+
+        let console_context = console_context.clone();
+        let node = node.clone();
+        let task_args = task_args.to_vec();
+
+        // The service blocks until it exits, just like any other command
+        let exited = task::spawn_blocking({
+            let processes = processes.clone();
+            let log_matched = log_matched.clone();
+            let console_output = console_output.clone();
+
+            move || {
+                let mut on_line = |stream: Stream, line: &[u8]| {
+                    if let Some(log_pattern) = &log_pattern {
+                        if log_pattern.is_match(&String::from_utf8_lossy(line)) {
+                            log_matched.store(true, Ordering::SeqCst);
+                        }
+                    }
+
+                    match output_mode {
+                        OutputMode::Grouped => {
+                            let mut line = line.to_vec();
+
+                            line.push(b'\n');
+
+                            console_output
+                                .lock()
+                                .expect("service output mutex is poisoned")
+                                .push(stream, start.elapsed(), &line);
+                        }
+                        OutputMode::Stream => {
+                            console_context.log_exec_output_line(
+                                &node.task_path,
+                                None,
+                                stream,
+                                line,
+                            );
+                        }
+                    }
+                };
+
+                handlers::handle_execution(
+                    &node.task_path,
+                    &node.task,
+                    &task_args,
+                    node.directory.as_deref(),
+                    &environment,
+                    &processes,
+                    Some(&mut on_line),
+                )
+            }
+        });
+
+        Ok(Self {
+            processes,
+            exited,
+            log_matched,
+            console_output,
+            start,
+        })
+    }
+
+    /// Stops the service the same way as a cancelled task, killing it if it does not exit in time
+    async fn stop(mut self) -> Result<Output, WorkbenchError> {
+        self.processes.cancel(libc::SIGTERM);
+
+        let result = if let Ok(result) =
+            time::timeout(processes::KILL_GRACE_PERIOD, &mut self.exited).await
+        {
+            result
+        } else {
+            self.processes.kill();
+
+            (&mut self.exited).await
+        };
+
+        Ok(self.finish(result??))
+    }
+
+    /// Gives the output of the service once it has exited the output that was kept while it ran
+    fn finish(&self, mut output: Output) -> Output {
+        output.console_output = mem::take(
+            &mut self
+                .console_output
+                .lock()
+                .expect("service output mutex is poisoned"),
+        );
+
+        output
+    }
+}
+
+/// Waits until every one of a service's readiness checks passes
+async fn wait_until_ready(
+    service: &Service,
+    node: &Node,
+    ready: &Ready,
+    processes: &Processes,
+) -> Result<Readiness, WorkbenchError> {
+    let timeout = ready.timeout.map(Duration::from_secs);
+
+    loop {
+        if service.exited.is_finished() {
+            return Ok(Readiness::Exited);
+        }
+
+        if processes.is_cancelled() {
+            return Ok(Readiness::Cancelled);
+        }
+
+        if let Some(timeout) = timeout {
+            if service.start.elapsed() >= timeout {
+                return Ok(Readiness::TimedOut(timeout));
+            }
+        }
+
+        // The probe command is the most expensive check, so it only runs once the others pass
+        if service.log_matched.load(Ordering::SeqCst)
+            && is_port_open(ready.port).await
+            && probe(node, ready, processes).await?
+        {
+            return Ok(Readiness::Ready);
+        }
+
+        time::sleep(READY_POLL_INTERVAL).await;
+    }
+}
+
+async fn is_port_open(port: Option<u16>) -> bool {
+    match port {
+        Some(port) => TcpStream::connect(("localhost", port)).await.is_ok(),
+        None => true,
+    }
+}
+
+/// Runs a service's `ready.run` command, returning whether it succeeded
+async fn probe(node: &Node, ready: &Ready, processes: &Processes) -> Result<bool, WorkbenchError> {
+    let Some(command) = &ready.run else {
+        return Ok(true);
+    };
+
+    let node = node.clone();
+    let processes = processes.clone();
+
+    // A probe that hangs should not keep the service waiting for longer than it is allowed to
+    let probe = conditions::get_probe_task(&node.task, command);
+    let probe = Task {
+        timeout: ready.timeout,
+        idle_timeout: None,
+        ..probe
+    };
+
+    task::spawn_blocking(move || {
+        let output = handlers::handle_execution(
+            &node.task_path,
+            &probe,
+            &[],
+            node.directory.as_deref(),
+            &node.environment,
+            &processes,
+            None,
+        )?;
+
+        Ok(output.succeeded(&probe))
+    })
+    .await?
+}
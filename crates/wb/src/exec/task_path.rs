@@ -13,9 +13,8 @@
 // You should have received a copy of the GNU General Public License along with Workbench. If not,
 // see <https://www.gnu.org/licenses/>.
 
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf, sync::LazyLock};
 
-use lazy_static::lazy_static;
 use regex::Regex;
 
 use crate::{
@@ -23,10 +22,9 @@ use crate::{
     error::WorkbenchError,
 };
 
-lazy_static! {
-    static ref TASK_PATH_REGEX: Regex =
-        Regex::new(r"^(?<namespace>[\pL-]*:)?(?<name>[\pL-]+)(?<property>\.[\pL-]+)?$").unwrap();
-}
+static TASK_PATH_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?<namespace>[\pL-]*:)?(?<name>[\pL-]+)(?<property>\.[\pL-]+)?$").unwrap()
+});
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct TaskPath {
@@ -129,38 +127,21 @@ pub fn get_task_at_path<'config>(
     }
 }
 
-pub fn count_dependencies_of_path(
-    config: &Config,
-    task_path: &TaskPath,
-) -> Result<Option<u64>, WorkbenchError> {
-    Ok(match get_task_at_path(config, task_path) {
-        Some(task) => {
-            let mut count: u64 = 0;
-
-            if let Some(ref dependencies) = task.dependencies {
-                count += dependencies.len() as u64;
-
-                for dependency in dependencies {
-                    match count_dependencies_of_path(
-                        config,
-                        &TaskPath::parse(dependency.as_str())?,
-                    )? {
-                        Some(dependencies_count) => {
-                            count += dependencies_count;
-                        }
-                        None => return Ok(None),
-                    }
-                }
-            }
-
-            Some(count)
-        }
-        None => None,
-    })
+/// The directory that a task runs in and resolves its files relative to, or `None` for the current
+/// directory
+pub fn get_task_directory(config: &Config, task: &Task) -> Option<PathBuf> {
+    match (&config.directory, &task.cwd) {
+        (Some(directory), Some(cwd)) => Some(directory.join(cwd)),
+        (Some(directory), None) => Some(directory.clone()),
+        (None, Some(cwd)) => Some(PathBuf::from(cwd)),
+        (None, None) => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::config::Run;
+
     use super::*;
 
     #[test]
@@ -224,4 +205,39 @@ mod tests {
     fn test_task_path_parse_illegal() {
         assert!(TaskPath::parse("a.b`").is_err());
     }
+
+    #[test]
+    fn test_get_task_directory() {
+        let mut config = Config {
+            ..Default::default()
+        };
+
+        let mut task = Task {
+            run: Some(Run::Args(vec!["true".to_owned()])),
+            ..Default::default()
+        };
+
+        assert_eq!(get_task_directory(&config, &task), None);
+
+        task.cwd = Some("sub".to_owned());
+
+        assert_eq!(
+            get_task_directory(&config, &task),
+            Some(PathBuf::from("sub"))
+        );
+
+        config.directory = Some(PathBuf::from("/project"));
+
+        assert_eq!(
+            get_task_directory(&config, &task),
+            Some(PathBuf::from("/project/sub"))
+        );
+
+        task.cwd = None;
+
+        assert_eq!(
+            get_task_directory(&config, &task),
+            Some(PathBuf::from("/project"))
+        );
+    }
 }
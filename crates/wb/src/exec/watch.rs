@@ -0,0 +1,516 @@
+// Copyright 2024 Sophie Katz
+//
+// This file is part of Workbench.
+//
+// Workbench is free software: you can redistribute it and/or modify it under the terms of the GNU
+// General Public License as published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// Workbench is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Workbench. If not,
+// see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+    runtime::Runtime,
+    signal::unix::{signal, SignalKind},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time,
+};
+
+use crate::{
+    config::{self, Config},
+    console::{Level, Log, OutputMode, Progress},
+    error::WorkbenchError,
+};
+
+use super::{
+    exec_graph, files, processes, FailureMode, Jobs, Outcome, Processes, TaskGraph, TaskPath,
+};
+
+/// How long to wait for changes to stop coming in before running again, so that saving several
+/// files at once only runs the tasks once
+const DEBOUNCE_PERIOD: Duration = Duration::from_millis(200);
+
+/// What has to run again after some of the watched paths have changed
+enum Change {
+    /// The configuration file changed, so everything runs again with the new configuration
+    Config,
+    /// The inputs of the tasks at these indices changed
+    Inputs(Vec<usize>),
+}
+
+/// Runs the target task, then runs it again whenever the inputs of it or its dependencies change
+/// until Workbench is interrupted
+///
+/// Only the tasks whose inputs changed, the tasks that did not succeed last time and the tasks that
+/// depend on them run again. If something changes while the tasks are still running, they are
+/// cancelled first. Changes to the configuration file reload it, and a configuration that fails to
+/// load is reported and ignored.
+pub fn watch<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    config: Config,
+    console_context: &ConsoleContext,
+    target_task_path: &TaskPath,
+    task_args: &[String],
+    jobs: u32,
+    output_mode: OutputMode,
+    failure_mode: FailureMode,
+) -> Result<(), WorkbenchError> {
+    let jobs = Jobs::new(jobs);
+
+    Runtime::new()?.block_on(watch_tasks(
+        config,
+        console_context,
+        target_task_path,
+        task_args,
+        &jobs,
+        output_mode,
+        failure_mode,
+    ))
+}
+
+async fn watch_tasks<ConsoleContext: 'static + Log + Progress + Clone + Send + Sync>(
+    config: Config,
+    console_context: &ConsoleContext,
+    target_task_path: &TaskPath,
+    task_args: &[String],
+    jobs: &Jobs,
+    output_mode: OutputMode,
+    failure_mode: FailureMode,
+) -> Result<(), WorkbenchError> {
+    let (sender, mut changes) = mpsc::unbounded_channel();
+
+    let mut config = config;
+    let mut graph = TaskGraph::build(&config, target_task_path)?;
+
+    graph.check_task_args(task_args)?;
+    let mut _watcher = watch_paths(&config, &graph, sender.clone())?;
+    let mut received = Vec::new();
+    let mut next_run = Some(graph.clone());
+    // The indices of the tasks that ran last time without succeeding
+    let mut unfinished = Vec::new();
+
+    loop {
+        let change = match next_run.take() {
+            Some(run_graph) => {
+                let processes = Processes::default();
+
+                let run = exec_graph(
+                    &run_graph,
+                    console_context,
+                    task_args,
+                    jobs,
+                    &processes,
+                    output_mode,
+                    failure_mode,
+                );
+
+                tokio::pin!(run);
+
+                let mut finished = None;
+
+                let change = tokio::select! {
+                    result = &mut run => {
+                        finished = Some(result);
+                        None
+                    }
+                    change = wait_for_changes(&mut changes, &mut received, &config, &graph) => {
+                        Some(change?)
+                    }
+                };
+
+                // Anything still running is out of date, and is stopped the same way as when the
+                // invocation is cancelled
+                let result = if let Some(result) = finished {
+                    result
+                } else {
+                    processes.cancel(libc::SIGTERM);
+
+                    if let Ok(result) = time::timeout(processes::KILL_GRACE_PERIOD, &mut run).await
+                    {
+                        result
+                    } else {
+                        processes.kill();
+                        run.await
+                    }
+                };
+
+                unfinished = find_unfinished(&graph, &run_graph, &result);
+
+                if change.is_none() {
+                    log_error(console_context, result)?;
+                } else if let Err(WorkbenchError::Interrupted) = result {
+                    return Err(WorkbenchError::Interrupted);
+                }
+
+                console_context.clear()?;
+
+                change
+            }
+            None => None,
+        };
+
+        let (change, paths) = if let Some(change) = change {
+            change
+        } else {
+            console_context.log_watch_waiting();
+
+            wait_while_idle(&mut changes, &mut received, &config, &graph).await?
+        };
+
+        console_context.log_watch_changed(&paths);
+
+        next_run = match change {
+            Change::Config => reload(console_context, &config, target_task_path, task_args).map(
+                |reloaded| -> Result<TaskGraph, WorkbenchError> {
+                    (config, graph) = reloaded;
+                    _watcher = watch_paths(&config, &graph, sender.clone())?;
+
+                    Ok(graph.clone())
+                },
+            ),
+            // Tasks that were cancelled or failed have to run again as well, or the tasks that
+            // depend on them would run without them having completed
+            Change::Inputs(mut affected) => {
+                affected.extend(&unfinished);
+
+                Some(Ok(graph.affected_subgraph(&affected)))
+            }
+        }
+        .transpose()?;
+    }
+}
+
+/// The indices in the graph of the tasks that ran without succeeding, which is all of them if the
+/// run stopped with an error
+fn find_unfinished(
+    graph: &TaskGraph,
+    run_graph: &TaskGraph,
+    result: &Result<(Outcome, Vec<usize>), WorkbenchError>,
+) -> Vec<usize> {
+    let succeeded = match result {
+        Ok((_, succeeded)) => succeeded.as_slice(),
+        Err(_) => &[],
+    };
+
+    run_graph
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !succeeded.contains(index))
+        .filter_map(|(_, node)| {
+            graph
+                .nodes()
+                .iter()
+                .position(|other| other.task_path == node.task_path)
+        })
+        .collect()
+}
+
+/// Logs an error that stopped the tasks from running, other than Workbench being interrupted
+fn log_error(
+    console_context: &impl Log,
+    result: Result<(Outcome, Vec<usize>), WorkbenchError>,
+) -> Result<(), WorkbenchError> {
+    match result {
+        // Failed tasks have already been logged
+        Ok(_) => Ok(()),
+        Err(WorkbenchError::Interrupted) => Err(WorkbenchError::Interrupted),
+        Err(err) => {
+            console_context.log_message(Level::Error, err);
+            Ok(())
+        }
+    }
+}
+
+/// Loads the configuration file again, returning `None` if it fails to load or the target task can
+/// no longer be run with it
+fn reload(
+    console_context: &impl Log,
+    config: &Config,
+    target_task_path: &TaskPath,
+    task_args: &[String],
+) -> Option<(Config, TaskGraph)> {
+    let path = config.path.as_ref()?;
+
+    let reloaded = config::load(path).and_then(|config| {
+        let graph = TaskGraph::build(&config, target_task_path)?;
+        graph.check_task_args(task_args)?;
+        Ok((config, graph))
+    });
+
+    match reloaded {
+        Ok(reloaded) => Some(reloaded),
+        Err(err) => {
+            console_context.log_message(
+                Level::Error,
+                format!("unable to reload configuration: {err}"),
+            );
+            None
+        }
+    }
+}
+
+/// Watches the inputs of every task in the graph and the configuration file, sending each path that
+/// changes
+fn watch_paths(
+    config: &Config,
+    graph: &TaskGraph,
+    sender: UnboundedSender<PathBuf>,
+) -> Result<RecommendedWatcher, WorkbenchError> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // Reading a file is not a change, and tasks read their inputs every time they run
+        if let Ok(event) = event {
+            if !matches!(event.kind, EventKind::Access(_)) {
+                for path in event.paths {
+                    // The receiver is only dropped once Workbench is exiting
+                    let _ = sender.send(path);
+                }
+            }
+        }
+    })?;
+
+    // Directories are only watched once, recursively if any pattern needs them to be
+    let mut roots = BTreeMap::new();
+
+    for node in graph.nodes() {
+        if let Some(inputs) = &node.task.inputs {
+            for (root, recursive) in files::resolve_watch_roots(inputs, node.directory.as_deref()) {
+                *roots.entry(root).or_insert(false) |= recursive;
+            }
+        }
+    }
+
+    // Editors often replace files rather than writing to them, so the configuration file's
+    // directory is watched instead of the file itself
+    if let Some(directory) = config.path.as_deref().and_then(Path::parent) {
+        roots.entry(directory.to_path_buf()).or_insert(false);
+    }
+
+    for (root, recursive) in roots {
+        watcher.watch(
+            &root,
+            if recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            },
+        )?;
+    }
+
+    Ok(watcher)
+}
+
+/// Waits for changes while nothing is running, which is also when Workbench stops if it is
+/// interrupted
+async fn wait_while_idle(
+    changes: &mut UnboundedReceiver<PathBuf>,
+    received: &mut Vec<PathBuf>,
+    config: &Config,
+    graph: &TaskGraph,
+) -> Result<(Change, Vec<PathBuf>), WorkbenchError> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        change = wait_for_changes(changes, received, config, graph) => change,
+        Some(()) = interrupt.recv() => Err(WorkbenchError::Interrupted),
+        Some(()) = terminate.recv() => Err(WorkbenchError::Interrupted),
+    }
+}
+
+/// Waits until watched paths have changed and then stopped changing, returning what has to run
+/// again and the paths that changed
+///
+/// Paths are collected into `received` so that none are lost if this is cancelled part way through.
+/// Outputs of the tasks are ignored, since they change whenever the tasks run.
+async fn wait_for_changes(
+    changes: &mut UnboundedReceiver<PathBuf>,
+    received: &mut Vec<PathBuf>,
+    config: &Config,
+    graph: &TaskGraph,
+) -> Result<(Change, Vec<PathBuf>), WorkbenchError> {
+    loop {
+        if received.is_empty() {
+            match changes.recv().await {
+                Some(path) => received.push(path),
+                None => future::pending().await,
+            }
+        }
+
+        while let Ok(Some(path)) = time::timeout(DEBOUNCE_PERIOD, changes.recv()).await {
+            received.push(path);
+        }
+
+        let paths = received.drain(..).collect::<BTreeSet<_>>();
+
+        let mut relevant = Vec::new();
+        let mut affected = BTreeSet::new();
+        let mut config_changed = false;
+
+        for path in paths {
+            if config.path.as_ref() == Some(&path) {
+                config_changed = true;
+                relevant.push(path);
+                continue;
+            }
+
+            let affected_by_path = find_affected(graph, &path)?;
+
+            if !affected_by_path.is_empty() {
+                affected.extend(affected_by_path);
+                relevant.push(path);
+            }
+        }
+
+        if relevant.is_empty() {
+            continue;
+        }
+
+        let change = if config_changed {
+            Change::Config
+        } else {
+            Change::Inputs(affected.into_iter().collect())
+        };
+
+        return Ok((change, relevant));
+    }
+}
+
+/// The indices of the tasks that have the path as one of their inputs, unless it is also one of the
+/// outputs of a task
+fn find_affected(graph: &TaskGraph, path: &Path) -> Result<Vec<usize>, WorkbenchError> {
+    let mut affected = Vec::new();
+
+    for node in graph.nodes() {
+        if let Some(outputs) = &node.task.outputs {
+            if files::matches_path(outputs, node.directory.as_deref(), path)? {
+                return Ok(Vec::new());
+            }
+        }
+    }
+
+    for (index, node) in graph.nodes().iter().enumerate() {
+        if let Some(inputs) = &node.task.inputs {
+            if files::matches_path(inputs, node.directory.as_deref(), path)? {
+                affected.push(index);
+            }
+        }
+    }
+
+    Ok(affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use crate::{
+        config::{Files, Run, Task},
+        exec::tests::{MockConsoleContext, TaskOrderEntry},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_reruns_cancelled_dependency() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        fs::write(temp_dir.path().join("a.txt"), "").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "").unwrap();
+
+        let config = Config {
+            tasks: Some(HashMap::from([
+                (
+                    "a".to_owned(),
+                    Task {
+                        run: Some(Run::String("sleep 2".to_owned())),
+                        inputs: Some(Files::List(vec!["a.txt".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    Task {
+                        run: Some(Run::String("true".to_owned())),
+                        inputs: Some(Files::List(vec!["b.txt".to_owned()])),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "c".to_owned(),
+                    Task {
+                        dependencies: Some(vec!["a".to_owned(), "b".to_owned()]),
+                        ..Default::default()
+                    },
+                ),
+            ])),
+            directory: Some(temp_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let console_context = MockConsoleContext::default();
+
+        let jobs = Jobs::new(4);
+
+        let target_task_path = TaskPath::parse("c").unwrap();
+
+        let watching = watch_tasks(
+            config,
+            &console_context,
+            &target_task_path,
+            &[],
+            &jobs,
+            OutputMode::Grouped,
+            FailureMode::FinishRunning,
+        );
+
+        let wait_for = |entry: TaskOrderEntry| {
+            let console_context = console_context.clone();
+
+            async move {
+                while !console_context.task_order().contains(&entry) {
+                    time::sleep(Duration::from_millis(50)).await;
+                }
+            }
+        };
+
+        let change_while_running = async {
+            wait_for(TaskOrderEntry::Begin("a".to_owned())).await;
+
+            fs::write(temp_dir.path().join("b.txt"), "changed").unwrap();
+
+            wait_for(TaskOrderEntry::Complete("c".to_owned())).await;
+        };
+
+        tokio::select! {
+            result = watching => panic!("stopped watching: {result:?}"),
+            result = time::timeout(Duration::from_secs(10), change_while_running) => result.unwrap(),
+        }
+
+        let task_order = console_context.task_order();
+
+        // Only the inputs of 'b' changed, but 'a' was cancelled so it has to run again before 'c'
+        assert_eq!(
+            task_order
+                .iter()
+                .filter(|entry| **entry == TaskOrderEntry::Begin("a".to_owned()))
+                .count(),
+            2
+        );
+        assert_eq!(
+            task_order.last(),
+            Some(&TaskOrderEntry::Complete("c".to_owned()))
+        );
+    }
+}
@@ -31,9 +31,13 @@ use clap::CommandFactory;
 use cli::{TaskArgs, WorkbenchArgs};
 use config::{resolve_path, Config};
 use console::{Context, Level, Log, Logger};
-use exec::TaskPath;
+use error::WorkbenchError;
+use exec::{FailureMode, TaskPath};
 use std::{env, path::PathBuf, process::exit};
 
+/// The conventional exit code for a program stopped by Ctrl-C
+const EXIT_CODE_INTERRUPTED: i32 = 130;
+
 fn main() {
     let (workbench_args, task_args) = cli::parse_args();
 
@@ -60,30 +64,69 @@ fn main() {
     }
 
     #[allow(clippy::bool_to_int_with_if)]
-    if let Some(succeeded) = builtin::try_exec_builtin_task(&config, &target_task_path) {
+    if let Some(succeeded) = builtin::try_exec_builtin_task(&logger, &config, &target_task_path) {
         exit(if succeeded { 0 } else { 1 });
     }
 
-    if target_task_path.property.is_some() {
+    if let Some(property) = &target_task_path.property {
         logger.log_message(
             Level::Error,
-            format!(
-                "unknown property {:?} for task {target_task_path}",
-                target_task_path.property.as_ref().unwrap()
-            ),
+            format!("unknown property {property:?} for task {target_task_path}"),
         );
         exit(1);
     }
 
+    if workbench_args.dry_run {
+        if let Err(err) = exec::dry_run(&config, &logger, &target_task_path, &task_args.task_args) {
+            logger.log_message(Level::Error, err);
+            exit(1);
+        }
+
+        return;
+    }
+
+    if workbench_args.watch {
+        // Progress bars would only ever show the first run
+        let console_context = Context::new(logger, None);
+
+        match exec::watch(
+            config,
+            &console_context,
+            &target_task_path,
+            &task_args.task_args,
+            workbench_args.jobs,
+            workbench_args.output,
+            resolve_failure_mode(&workbench_args),
+        ) {
+            Ok(()) => {}
+            Err(WorkbenchError::Interrupted) => exit(EXIT_CODE_INTERRUPTED),
+            Err(e) => {
+                console_context.log_message(Level::Error, e.to_string());
+                exit(1);
+            }
+        }
+
+        return;
+    }
+
     let console_context =
         create_console_context(&workbench_args, logger, &config, &target_task_path);
 
-    match exec::exec(config, &console_context, target_task_path) {
-        Ok(succeeded) => {
-            if !succeeded {
-                exit(1);
+    match exec::exec(
+        &config,
+        &console_context,
+        &target_task_path,
+        &task_args.task_args,
+        workbench_args.jobs,
+        workbench_args.output,
+        resolve_failure_mode(&workbench_args),
+    ) {
+        Ok(outcome) => {
+            if !outcome.succeeded() {
+                exit(outcome.exit_code());
             }
         }
+        Err(WorkbenchError::Interrupted) => exit(EXIT_CODE_INTERRUPTED),
         Err(e) => {
             console_context.log_message(Level::Error, e.to_string());
             exit(1);
@@ -154,20 +197,29 @@ fn load_config(logger: &impl Log, workbench_args: &WorkbenchArgs) -> Config {
 }
 
 fn resolve_target_task_path(logger: &impl Log, task_args: &TaskArgs) -> TaskPath {
-    match &task_args.target_task_path {
-        Some(path_string) => match TaskPath::parse(path_string.as_str()) {
+    if let Some(path_string) = &task_args.target_task_path {
+        match TaskPath::parse(path_string.as_str()) {
             Ok(task_path) => task_path,
             Err(err) => {
                 logger.log_message(Level::Error, err);
                 exit(1);
             }
-        },
-        None => {
-            logger.log_message(Level::Error, "no target specified");
-            println!();
-            WorkbenchArgs::command().print_help().unwrap();
-            exit(1);
         }
+    } else {
+        logger.log_message(Level::Error, "no target specified");
+        println!();
+        WorkbenchArgs::command().print_help().unwrap();
+        exit(1);
+    }
+}
+
+const fn resolve_failure_mode(workbench_args: &WorkbenchArgs) -> FailureMode {
+    if workbench_args.fail_fast {
+        FailureMode::FailFast
+    } else if workbench_args.keep_going {
+        FailureMode::KeepGoing
+    } else {
+        FailureMode::FinishRunning
     }
 }
 
@@ -181,8 +233,8 @@ fn create_console_context(
         return Context::new(logger, None);
     }
 
-    let progress_bar_ticks = 1 + match exec::count_dependencies_of_path(config, target_task_path) {
-        Ok(count) => count.unwrap_or(0),
+    let progress_bar_ticks = match exec::count_dependencies_of_path(config, target_task_path) {
+        Ok(count) => 1 + count,
         Err(e) => {
             logger.log_message(Level::Error, e.to_string());
             exit(1);